@@ -0,0 +1,156 @@
+use crate::utils::{
+    antiraid::{self, RaidAction},
+    guildconfig::{self, GuildConfigKey},
+    timeparser::HumanDuration,
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+
+#[command("status")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[description("View raid detection and whether raid mode is on")]
+async fn raid_status(ctx: &Context, msg: &Message) -> CommandResult {
+    let raid = guildconfig::settings(ctx, msg.guild_id.unwrap()).await.raid;
+    let detection = if raid.detect {
+        format!(
+            "More than {} joins in {} seconds, or that many similar or new accounts. New members are {}",
+            raid.joins,
+            raid.seconds,
+            raid.action.past_tense()
+        )
+    } else {
+        "Off".to_string()
+    };
+    let mode = match &raid.active {
+        Some(active) => format!("On since <t:{}:R>: {}", active.since, active.reason),
+        None => "Off".to_string(),
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Anti-Raid");
+                e.field("Detection", detection, false);
+                e.field("Raid Mode", mode, false);
+                e.color(if raid.active.is_some() {
+                    Colour::RED
+                } else {
+                    Colour::BLURPLE
+                });
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command("setup")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(1)]
+#[usage = "<off | joins seconds kick|quarantine>"]
+#[example = "10 10s kick"]
+#[example = "off"]
+#[description("Start raid mode automatically when more than `joins` members join within `seconds`, or that many share a name or are new accounts without an avatar")]
+async fn raid_setup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    if args.rest().trim().eq_ignore_ascii_case("off") {
+        {
+            let mut data = ctx.data.write().await;
+            let store = data
+                .get_mut::<GuildConfigKey>()
+                .expect("Expected GuildConfig in TypeMap.");
+            store.get_mut(guild).raid.detect = false;
+            store.save().await?;
+        }
+        msg.channel_id.say(&ctx, "Raid detection is off").await?;
+        return Ok(());
+    }
+    let joins = args.single::<u32>().ok().filter(|j| *j > 0);
+    let seconds = args
+        .single::<HumanDuration>()
+        .ok()
+        .map(|d| d.whole_seconds());
+    let action = args.rest().trim().parse::<RaidAction>();
+    let (joins, seconds, action) = match (joins, seconds, action) {
+        (Some(j), Some(s), Ok(a)) => (j, s, a),
+        (_, _, Err(why)) if !args.rest().trim().is_empty() => {
+            msg.channel_id.say(&ctx, why).await?;
+            return Ok(());
+        }
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx,
+                    "Usage: `raid setup <off | joins seconds kick|quarantine>`",
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let raid = &mut store.get_mut(guild).raid;
+        raid.detect = true;
+        raid.joins = joins;
+        raid.seconds = seconds;
+        raid.action = action;
+        store.save().await?;
+    }
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "Raid mode will start after more than {} suspicious joins in {} seconds, and new members will be {}",
+                joins,
+                seconds,
+                action.past_tense()
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command("on")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[usage = "[reason]"]
+#[description("Turn raid mode on by hand")]
+async fn raid_on(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let reason = match args.rest().trim() {
+        "" => format!("Started by {}", msg.author.tag()),
+        r => format!("Started by {}: {}", msg.author.tag(), r),
+    };
+    if !antiraid::start(ctx, msg.guild_id.unwrap(), reason).await? {
+        msg.channel_id.say(&ctx, "Raid mode is already on").await?;
+    } else {
+        msg.channel_id.say(&ctx, "Raid mode is on").await?;
+    }
+    Ok(())
+}
+
+#[command("off")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[description("End raid mode and restore the verification level")]
+async fn raid_off(ctx: &Context, msg: &Message) -> CommandResult {
+    let reply = match antiraid::end(ctx, msg.guild_id.unwrap()).await? {
+        Some(raid) => format!(
+            "Raid mode is off. It started <t:{}:R>{}",
+            raid.since,
+            if raid.previous_level.is_some() {
+                ". The verification level was restored"
+            } else {
+                ""
+            }
+        ),
+        None => "Raid mode isn't on".to_string(),
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use crate::utils::{appeals, moderation};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+#[command]
+#[only_in("dms")]
+#[usage = "[server id] <why you should be unbanned>"]
+#[example = "I didn't read the rules, it won't happen again"]
+#[description("Appeal a ban. Send this to the bot in DMs; staff accept or deny it and you'll be told the outcome")]
+async fn appeal(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut open = appeals::appealable(ctx, msg.author.id).await;
+    if open.is_empty() {
+        msg.channel_id
+            .say(&ctx, "You don't have a ban that can be appealed")
+            .await?;
+        return Ok(());
+    }
+    let chosen = args
+        .current()
+        .and_then(|a| a.parse::<u64>().ok())
+        .and_then(|id| open.iter().position(|(g, _)| g.0 == id));
+    let (guild, case) = match chosen {
+        Some(i) => {
+            args.advance();
+            open.swap_remove(i)
+        }
+        None if open.len() == 1 => open.remove(0),
+        None => {
+            let mut servers = String::new();
+            for (guild, _) in &open {
+                servers.push_str(&format!(
+                    "\n`{}` {}",
+                    guild.0,
+                    moderation::guild_name(ctx, *guild).await
+                ));
+            }
+            msg.channel_id
+                .say(
+                    &ctx,
+                    format!(
+                        "You're banned from more than one server. Start your appeal with the server id:{}",
+                        servers
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    let text = args.rest().trim();
+    if text.is_empty() {
+        msg.channel_id
+            .say(
+                &ctx,
+                "Tell staff why you should be unbanned: `appeal <message>`",
+            )
+            .await?;
+        return Ok(());
+    }
+    if text.chars().count() > appeals::MAX_LENGTH {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("Appeals can be at most {} characters", appeals::MAX_LENGTH),
+            )
+            .await?;
+        return Ok(());
+    }
+    if let Some(why) = appeals::refusal(&case) {
+        msg.channel_id.say(&ctx, why).await?;
+        return Ok(());
+    }
+    appeals::submit(ctx, guild, &case, &msg.author, text.to_string()).await?;
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "Your appeal was sent to the staff of **{}**. I'll message you when it's been reviewed",
+                moderation::guild_name(ctx, guild).await
+            ),
+        )
+        .await?;
+    Ok(())
+}
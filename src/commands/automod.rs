@@ -0,0 +1,252 @@
+use crate::utils::{
+    automod::{AutomodAction, AutomodSettings, Filter},
+    guildconfig::GuildConfigKey,
+};
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::fmt::Write;
+
+/// Applies `change` to the guild's automod settings and saves them.
+async fn update(
+    ctx: &Context,
+    guild: GuildId,
+    change: impl FnOnce(&mut AutomodSettings),
+) -> CommandResult {
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.");
+    change(&mut store.get_mut(guild).automod);
+    store.save().await?;
+    Ok(())
+}
+
+enum Exemption {
+    Channel(ChannelId),
+    Role(RoleId),
+}
+
+fn parse_exemption(raw: &str) -> Option<Exemption> {
+    if raw.starts_with("<#") {
+        raw.parse::<ChannelId>().ok().map(Exemption::Channel)
+    } else {
+        raw.parse::<RoleId>().ok().map(Exemption::Role)
+    }
+}
+
+#[command("show")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("View this server's automod rules")]
+async fn automod_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let settings = {
+        let data = ctx.data.read().await;
+        data.get::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.")
+            .get(msg.guild_id.unwrap())
+            .automod
+    };
+    let mut rules = String::new();
+    for filter in Filter::ALL.iter().copied() {
+        let rule = settings.rule(filter);
+        if rule.enabled {
+            writeln!(
+                rules,
+                "**{}**: {} at {} {}",
+                filter,
+                rule.action,
+                rule.threshold,
+                filter.threshold_help()
+            )?;
+        } else {
+            writeln!(rules, "**{}**: off", filter)?;
+        }
+    }
+    let exempt = settings
+        .exempt_channels
+        .iter()
+        .map(|c| format!("<#{}>", c.0))
+        .chain(settings.exempt_roles.iter().map(|r| format!("<@&{}>", r.0)))
+        .collect::<Vec<String>>();
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(if settings.enabled {
+                    "Automod (on)"
+                } else {
+                    "Automod (off)"
+                });
+                e.description(rules);
+                e.field(
+                    "Exempt",
+                    if exempt.is_empty() {
+                        "Nothing".to_string()
+                    } else {
+                        exempt.join(", ")
+                    },
+                    false,
+                );
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command("on")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("Turn automod on")]
+async fn automod_on(ctx: &Context, msg: &Message) -> CommandResult {
+    update(ctx, msg.guild_id.unwrap(), |a| a.enabled = true).await?;
+    msg.channel_id
+        .say(&ctx, "Automod is on. Pick filters with `automod rule`")
+        .await?;
+    Ok(())
+}
+
+#[command("off")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("Turn automod off")]
+async fn automod_off(ctx: &Context, msg: &Message) -> CommandResult {
+    update(ctx, msg.guild_id.unwrap(), |a| a.enabled = false).await?;
+    msg.channel_id.say(&ctx, "Automod is off").await?;
+    Ok(())
+}
+
+#[command("rule")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(2)]
+#[usage = "<filter> <off | threshold action>"]
+#[example = "mentions 5 mute 10m"]
+#[example = "invites 1 delete"]
+#[example = "caps off"]
+#[description("Configure a filter: `repeats`, `mentions`, `invites`, `caps` or `emoji`. Actions are `delete`, `warn`, `mute <duration>` and `kick`")]
+async fn automod_rule(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let filter = match args.single::<Filter>() {
+        Ok(f) => f,
+        Err(e) => {
+            let reason = match e {
+                ArgError::Parse(why) => why,
+                _ => "Usage: `automod rule <filter> <off | threshold action>`".to_string(),
+            };
+            msg.channel_id.say(&ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    let guild = msg.guild_id.unwrap();
+    if args.rest().trim().eq_ignore_ascii_case("off") {
+        update(ctx, guild, |a| a.rule_mut(filter).enabled = false).await?;
+        msg.channel_id
+            .say(&ctx, format!("Turned off the {} filter", filter))
+            .await?;
+        return Ok(());
+    }
+    let threshold = match args.single::<u32>() {
+        Ok(t) if t > 0 => t,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx,
+                    format!(
+                        "The threshold must be a number above zero: {}",
+                        filter.threshold_help()
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    let action = match args.rest().parse::<AutomodAction>() {
+        Ok(a) => a,
+        Err(why) => {
+            msg.channel_id.say(&ctx, why).await?;
+            return Ok(());
+        }
+    };
+    update(ctx, guild, |a| {
+        let rule = a.rule_mut(filter);
+        rule.enabled = true;
+        rule.threshold = threshold;
+        rule.action = action;
+    })
+    .await?;
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "The {} filter will {} at {} {}",
+                filter,
+                action,
+                threshold,
+                filter.threshold_help()
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command("exempt")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<#channel|@role>"]
+#[description("Stop automod from checking a channel or members with a role")]
+async fn automod_exempt(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = match parse_exemption(&args.single::<String>()?) {
+        Some(Exemption::Channel(c)) => {
+            update(ctx, msg.guild_id.unwrap(), |a| {
+                if !a.exempt_channels.contains(&c) {
+                    a.exempt_channels.push(c);
+                }
+            })
+            .await?;
+            format!("Automod will ignore <#{}>", c.0)
+        }
+        Some(Exemption::Role(r)) => {
+            update(ctx, msg.guild_id.unwrap(), |a| {
+                if !a.exempt_roles.contains(&r) {
+                    a.exempt_roles.push(r);
+                }
+            })
+            .await?;
+            format!("Automod will ignore members with <@&{}>", r.0)
+        }
+        None => "Couldn't find that channel or role".to_string(),
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("unexempt")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<#channel|@role>"]
+#[description("Have automod check a channel or role again")]
+async fn automod_unexempt(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = match parse_exemption(&args.single::<String>()?) {
+        Some(Exemption::Channel(c)) => {
+            update(ctx, msg.guild_id.unwrap(), |a| {
+                a.exempt_channels.retain(|e| *e != c)
+            })
+            .await?;
+            format!("Automod will check <#{}> again", c.0)
+        }
+        Some(Exemption::Role(r)) => {
+            update(ctx, msg.guild_id.unwrap(), |a| {
+                a.exempt_roles.retain(|e| *e != r)
+            })
+            .await?;
+            format!("Automod will check members with <@&{}> again", r.0)
+        }
+        None => "Couldn't find that channel or role".to_string(),
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use crate::utils::cases::{self, CaseKey, MAX_REASON_LEN};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+
+/// The most cases `cases` lists at once, newest first.
+const CASE_LIST_LIMIT: usize = 20;
+/// Reasons in `cases` are cut to this many characters.
+const CASE_LIST_REASON_LEN: usize = 100;
+/// Discord's limit for an embed description.
+const DESCRIPTION_LIMIT: usize = 4096;
+
+#[command("case")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[num_args(1)]
+#[usage = "<number>"]
+#[description("Show a moderation case")]
+async fn case_show(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let number = match args.single::<u64>() {
+        Ok(n) => n,
+        Err(_) => {
+            msg.channel_id.say(&ctx, "Usage: `case <number>`").await?;
+            return Ok(());
+        }
+    };
+    let case = {
+        let data = ctx.data.read().await;
+        data.get::<CaseKey>()
+            .expect("Expected Cases in TypeMap.")
+            .get(msg.guild_id.unwrap(), number)
+            .cloned()
+    };
+    match case {
+        Some(case) => {
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.embed(|e| {
+                        case.embed(e);
+                        e
+                    })
+                })
+                .await?;
+        }
+        None => {
+            msg.channel_id
+                .say(&ctx, format!("There is no case #{}", number))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[command("cases")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[num_args(1)]
+#[usage = "<@user>"]
+#[description("List the moderation history of a user")]
+async fn case_list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match args.single::<UserId>() {
+        Ok(u) => u,
+        Err(_) => {
+            msg.channel_id.say(&ctx, "Usage: `cases <@user>`").await?;
+            return Ok(());
+        }
+    };
+    let (total, lines) = {
+        let data = ctx.data.read().await;
+        let cases = data
+            .get::<CaseKey>()
+            .expect("Expected Cases in TypeMap.")
+            .for_user(msg.guild_id.unwrap(), user);
+        let mut lines = Vec::new();
+        let mut length = 0;
+        for c in cases.iter().rev().take(CASE_LIST_LIMIT) {
+            let line = format!(
+                "`#{}` **{}** <t:{}:d> by <@{}>: {}",
+                c.number,
+                c.action,
+                c.created_at,
+                c.moderator.0,
+                cases::shorten(
+                    c.reason.as_deref().unwrap_or("No reason given"),
+                    CASE_LIST_REASON_LEN
+                )
+            );
+            // One more for the newline joining it to the previous line.
+            length += line.chars().count() + 1;
+            if length > DESCRIPTION_LIMIT {
+                break;
+            }
+            lines.push(line);
+        }
+        (cases.len(), lines)
+    };
+    let description = if lines.is_empty() {
+        format!("<@{}> has a clean record", user.0)
+    } else {
+        lines.join("\n")
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Moderation History");
+                e.description(description);
+                if total > lines.len() && !lines.is_empty() {
+                    e.footer(|f| {
+                        f.text(format!(
+                            "Showing the latest {} of {} cases",
+                            lines.len(),
+                            total
+                        ))
+                    });
+                }
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command("reason")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[min_args(2)]
+#[usage = "<number> <new reason>"]
+#[description("Change the reason on a moderation case")]
+async fn case_reason(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let number = match args.single::<u64>() {
+        Ok(n) => n,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Usage: `reason <number> <new reason>`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let reason = args.rest().trim().to_string();
+    if reason.chars().count() > MAX_REASON_LEN {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("Reasons can be at most {} characters", MAX_REASON_LEN),
+            )
+            .await?;
+        return Ok(());
+    }
+    let updated = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<CaseKey>()
+            .expect("Expected Cases in TypeMap.");
+        match store.get_mut(msg.guild_id.unwrap(), number) {
+            Some(case) => {
+                case.reason = Some(reason);
+                let case = case.clone();
+                store.save().await?;
+                Some(case)
+            }
+            None => None,
+        }
+    };
+    match updated {
+        Some(case) => {
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.content(format!("Updated the reason for case #{}", number));
+                    m.embed(|e| {
+                        case.embed(e);
+                        e
+                    })
+                })
+                .await?;
+        }
+        None => {
+            msg.channel_id
+                .say(&ctx, format!("There is no case #{}", number))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,319 @@
+use crate::utils::{
+    locks::{self, LockKey},
+    modlog,
+    timeparser::HumanDuration,
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tracing::error;
+
+/// Discord's longest slowmode, six hours.
+const MAX_SLOWMODE: i64 = 6 * 60 * 60;
+
+enum Target {
+    Here,
+    Channel(ChannelId),
+    All,
+}
+
+/// Reads an optional `#channel` or `all` from the front of the arguments.
+fn target(args: &mut Args) -> Target {
+    if args
+        .current()
+        .map_or(false, |a| a.eq_ignore_ascii_case("all"))
+    {
+        args.advance();
+        return Target::All;
+    }
+    match args.single::<ChannelId>() {
+        Ok(c) => Target::Channel(c),
+        Err(_) => Target::Here,
+    }
+}
+
+/// The text channels a target covers. Channels outside `guild` are dropped.
+async fn resolve(
+    ctx: &Context,
+    guild: GuildId,
+    here: ChannelId,
+    target: &Target,
+) -> serenity::Result<Vec<GuildChannel>> {
+    let channel = match target {
+        Target::All => {
+            return Ok(guild
+                .channels(&ctx.http)
+                .await?
+                .into_iter()
+                .map(|(_, c)| c)
+                .filter(|c| matches!(c.kind, ChannelType::Text | ChannelType::News))
+                .collect())
+        }
+        Target::Here => here,
+        Target::Channel(c) => *c,
+    };
+    Ok(channel
+        .to_channel(ctx)
+        .await
+        .ok()
+        .and_then(|c| c.guild())
+        .filter(|c| c.guild_id == guild)
+        .into_iter()
+        .collect())
+}
+
+fn reason_text(reason: Option<&str>) -> String {
+    reason.map_or_else(String::new, |r| format!(": {}", r))
+}
+
+async fn lock_channels(
+    ctx: &Context,
+    msg: &Message,
+    target: Target,
+    reason: Option<&str>,
+) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let channels = resolve(ctx, guild, msg.channel_id, &target).await?;
+    if channels.is_empty() {
+        msg.channel_id
+            .say(&ctx, "Couldn't find that channel")
+            .await?;
+        return Ok(());
+    }
+    let mut locked = Vec::new();
+    for channel in channels {
+        let already = {
+            let data = ctx.data.read().await;
+            data.get::<LockKey>()
+                .expect("Expected Locks in TypeMap.")
+                .get(channel.id)
+                .is_some()
+        };
+        if already {
+            continue;
+        }
+        // Sent first, since the bot may not be able to talk once it's locked.
+        let _ = channel
+            .say(
+                &ctx.http,
+                format!(":lock: This channel is locked{}", reason_text(reason)),
+            )
+            .await;
+        match locks::lock(&ctx.http, &channel).await {
+            Ok(saved) => {
+                let mut data = ctx.data.write().await;
+                data.get_mut::<LockKey>()
+                    .expect("Expected Locks in TypeMap.")
+                    .insert(channel.id, saved);
+                locked.push(channel.id);
+            }
+            Err(why) => error!("Failed to lock {}: {:?}", channel.id, why),
+        }
+    }
+    {
+        let data = ctx.data.read().await;
+        data.get::<LockKey>()
+            .expect("Expected Locks in TypeMap.")
+            .save()
+            .await?;
+    }
+    let reply = match (&target, locked.len()) {
+        (_, 0) => "Nothing to lock. Already locked channels are skipped".to_string(),
+        (Target::All, n) => format!(":lock: Locked down {} channels", n),
+        _ => format!(":lock: Locked <#{}>", locked[0].0),
+    };
+    if !locked.is_empty() {
+        let title = match target {
+            Target::All => "Lockdown",
+            _ => "Channel Locked",
+        };
+        modlog::post_channels(
+            &ctx.http,
+            &ctx.data,
+            guild,
+            title,
+            msg.author.id,
+            &locked,
+            reason,
+        )
+        .await;
+    }
+    // The command channel itself may be locked by now.
+    let _ = msg.channel_id.say(&ctx, reply).await;
+    Ok(())
+}
+
+#[command]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[usage = "[#channel|all] [reason]"]
+#[example = "#general Cooling off"]
+#[description(
+    "Stop @everyone from sending messages. `unlock` restores the permission exactly as it was"
+)]
+async fn lock(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let target = target(&mut args);
+    let reason = Some(args.rest().trim()).filter(|r| !r.is_empty());
+    lock_channels(ctx, msg, target, reason).await
+}
+
+#[command]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[usage = "[reason]"]
+#[description("Lock every text channel at once. Revert it with `unlock all`")]
+async fn lockdown(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let reason = Some(args.rest().trim()).filter(|r| !r.is_empty());
+    lock_channels(ctx, msg, Target::All, reason).await
+}
+
+#[command]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[usage = "[#channel|all]"]
+#[description("Undo `lock` or `lockdown`")]
+async fn unlock(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let target = target(&mut args);
+    let pending = {
+        let data = ctx.data.read().await;
+        let store = data.get::<LockKey>().expect("Expected Locks in TypeMap.");
+        let channels = match target {
+            Target::All => store.locked_in(guild),
+            Target::Here => vec![msg.channel_id],
+            Target::Channel(c) => vec![c],
+        };
+        channels
+            .into_iter()
+            .filter_map(|c| store.get(c).filter(|s| s.guild == guild).map(|s| (c, s)))
+            .collect::<Vec<_>>()
+    };
+    if pending.is_empty() {
+        msg.channel_id
+            .say(
+                &ctx,
+                "Nothing to unlock. Only channels locked by the bot can be unlocked",
+            )
+            .await?;
+        return Ok(());
+    }
+    let mut unlocked = Vec::new();
+    for (channel, saved) in pending {
+        match locks::unlock(&ctx.http, channel, saved).await {
+            Ok(_) => {
+                let _ = channel
+                    .say(&ctx.http, ":unlock: This channel is unlocked")
+                    .await;
+                unlocked.push(channel);
+            }
+            Err(why) => error!("Failed to unlock {}: {:?}", channel, why),
+        }
+    }
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<LockKey>()
+            .expect("Expected Locks in TypeMap.");
+        for channel in &unlocked {
+            store.remove(*channel);
+        }
+        store.save().await?;
+    }
+    if !unlocked.is_empty() {
+        modlog::post_channels(
+            &ctx.http,
+            &ctx.data,
+            guild,
+            "Channel Unlocked",
+            msg.author.id,
+            &unlocked,
+            None,
+        )
+        .await;
+    }
+    let reply = match unlocked.len() {
+        0 => "Couldn't unlock anything. Check my permissions".to_string(),
+        1 => format!(":unlock: Unlocked <#{}>", unlocked[0].0),
+        n => format!(":unlock: Unlocked {} channels", n),
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[min_args(1)]
+#[max_args(2)]
+#[usage = "<duration|off> [#channel]"]
+#[example = "30s"]
+#[example = "off #general"]
+#[description("Set how long members wait between messages, up to 6 hours")]
+async fn slowmode(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let raw = args.single::<String>()?;
+    let seconds = if raw.eq_ignore_ascii_case("off") || raw == "0" {
+        0
+    } else {
+        match raw.parse::<HumanDuration>() {
+            Ok(d) if d.whole_seconds() <= MAX_SLOWMODE => d.whole_seconds(),
+            Ok(_) => {
+                msg.channel_id
+                    .say(&ctx, "Slowmode can be at most 6 hours")
+                    .await?;
+                return Ok(());
+            }
+            Err(why) => {
+                msg.channel_id.say(&ctx, why.to_string()).await?;
+                return Ok(());
+            }
+        }
+    };
+    let guild = msg.guild_id.unwrap();
+    let target = target(&mut args);
+    if let Target::All = target {
+        msg.channel_id
+            .say(&ctx, "Slowmode is set one channel at a time")
+            .await?;
+        return Ok(());
+    }
+    let channel = match resolve(ctx, guild, msg.channel_id, &target)
+        .await?
+        .into_iter()
+        .next()
+    {
+        Some(c) => c.id,
+        None => {
+            msg.channel_id
+                .say(&ctx, "Couldn't find that channel")
+                .await?;
+            return Ok(());
+        }
+    };
+    channel
+        .edit(&ctx.http, |c| c.slow_mode_rate(seconds as u64))
+        .await?;
+    let (title, reply) = if seconds == 0 {
+        (
+            "Slowmode Off",
+            format!("Slowmode is off in <#{}>", channel.0),
+        )
+    } else {
+        let length = HumanDuration(time::Duration::seconds(seconds));
+        (
+            "Slowmode",
+            format!("<#{}> now has a {} slowmode", channel.0, length),
+        )
+    };
+    modlog::post_channels(
+        &ctx.http,
+        &ctx.data,
+        guild,
+        title,
+        msg.author.id,
+        &[channel],
+        None,
+    )
+    .await;
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
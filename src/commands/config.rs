@@ -0,0 +1,344 @@
+use crate::utils::guildconfig::{self, GuildConfigKey, GuildSettings};
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::str::FromStr;
+
+#[derive(Clone, Copy)]
+enum Setting {
+    WelcomeChannel,
+    RulesChannel,
+    VerifyChannel,
+    RoleChannel,
+    ModLogChannel,
+    StaffChannel,
+    ModmailCategory,
+    UnverifiedRole,
+    UserRole,
+    MuteRole,
+}
+
+impl FromStr for Setting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "welcome" => Ok(Setting::WelcomeChannel),
+            "rules" => Ok(Setting::RulesChannel),
+            "verify" => Ok(Setting::VerifyChannel),
+            "role" | "roles" => Ok(Setting::RoleChannel),
+            "modlog" => Ok(Setting::ModLogChannel),
+            "staff" => Ok(Setting::StaffChannel),
+            "modmail" => Ok(Setting::ModmailCategory),
+            "unverified" => Ok(Setting::UnverifiedRole),
+            "user" => Ok(Setting::UserRole),
+            "mute" => Ok(Setting::MuteRole),
+            _ => Err(format!(
+                "`{}` is not a setting. Choose one of `welcome`, `rules`, `verify`, `role`, `modlog`, `staff`, `modmail`, `unverified`, `user` or `mute`",
+                s
+            )),
+        }
+    }
+}
+
+fn channel_text(channel: Option<ChannelId>) -> String {
+    match channel {
+        Some(c) => format!("<#{}>", c.0),
+        None => "Not set".to_string(),
+    }
+}
+
+fn role_text(role: Option<RoleId>) -> String {
+    match role {
+        Some(r) => format!("<@&{}>", r.0),
+        None => "Not set".to_string(),
+    }
+}
+
+#[command("show")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("View this server's configuration")]
+async fn config_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let settings: GuildSettings = {
+        let data = ctx.data.read().await;
+        data.get::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.")
+            .get(msg.guild_id.unwrap())
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("Server Configuration");
+                e.color(Colour::BLURPLE);
+                e.field(
+                    "Welcome Channel",
+                    channel_text(settings.welcome_channel),
+                    true,
+                );
+                e.field("Rules Channel", channel_text(settings.rules_channel), true);
+                e.field(
+                    "Verify Channel",
+                    channel_text(settings.verify_channel),
+                    true,
+                );
+                e.field("Role Channel", channel_text(settings.role_channel), true);
+                e.field(
+                    "Mod Log Channel",
+                    channel_text(settings.modlog_channel),
+                    true,
+                );
+                e.field("Staff Channel", channel_text(settings.staff_channel), true);
+                e.field(
+                    "Modmail Category",
+                    channel_text(settings.modmail_category),
+                    true,
+                );
+                e.field("Unverified Role", role_text(settings.unverified_role), true);
+                e.field("User Role", role_text(settings.user_role), true);
+                e.field("Mute Role", role_text(settings.mute_role), true);
+                e.field(
+                    "Appeal Message",
+                    settings.appeal_message.as_deref().unwrap_or("Not set"),
+                    false,
+                );
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command("set")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(2)]
+#[usage = "<setting> <#channel|@role>"]
+#[description("Set a channel or role for this server")]
+async fn config_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let setting = match args.single::<Setting>() {
+        Ok(s) => s,
+        Err(e) => {
+            let reason = match e {
+                ArgError::Parse(p) => p,
+                _ => "Provide a setting to change".to_string(),
+            };
+            msg.channel_id.say(&ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    let guild = msg.guild_id.unwrap();
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.");
+    let settings = store.get_mut(guild);
+    let reply = match setting {
+        Setting::WelcomeChannel
+        | Setting::RulesChannel
+        | Setting::VerifyChannel
+        | Setting::RoleChannel
+        | Setting::ModLogChannel
+        | Setting::StaffChannel
+        | Setting::ModmailCategory => match args.single::<ChannelId>() {
+            Err(_) => "Couldn't find that channel".to_string(),
+            Ok(channel) => {
+                match setting {
+                    Setting::WelcomeChannel => settings.welcome_channel = Some(channel),
+                    Setting::RulesChannel => settings.rules_channel = Some(channel),
+                    Setting::VerifyChannel => settings.verify_channel = Some(channel),
+                    Setting::ModLogChannel => settings.modlog_channel = Some(channel),
+                    Setting::StaffChannel => settings.staff_channel = Some(channel),
+                    Setting::ModmailCategory => settings.modmail_category = Some(channel),
+                    _ => settings.role_channel = Some(channel),
+                };
+                format!("Set to {}", channel_text(Some(channel)))
+            }
+        },
+        Setting::UnverifiedRole | Setting::UserRole | Setting::MuteRole => {
+            match args.single::<RoleId>() {
+                Err(_) => "Couldn't find that role".to_string(),
+                Ok(role) => {
+                    match setting {
+                        Setting::UnverifiedRole => settings.unverified_role = Some(role),
+                        Setting::UserRole => settings.user_role = Some(role),
+                        _ => settings.mute_role = Some(role),
+                    };
+                    format!("Set to {}", role_text(Some(role)))
+                }
+            }
+        }
+    };
+    store.save().await?;
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("unset")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<setting>"]
+#[description("Clear a channel or role for this server")]
+async fn config_unset(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let setting = match args.single::<Setting>() {
+        Ok(s) => s,
+        Err(e) => {
+            let reason = match e {
+                ArgError::Parse(p) => p,
+                _ => "Provide a setting to clear".to_string(),
+            };
+            msg.channel_id.say(&ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.");
+    let settings = store.get_mut(msg.guild_id.unwrap());
+    match setting {
+        Setting::WelcomeChannel => settings.welcome_channel = None,
+        Setting::RulesChannel => settings.rules_channel = None,
+        Setting::VerifyChannel => settings.verify_channel = None,
+        Setting::RoleChannel => settings.role_channel = None,
+        Setting::ModLogChannel => settings.modlog_channel = None,
+        Setting::StaffChannel => settings.staff_channel = None,
+        Setting::ModmailCategory => settings.modmail_category = None,
+        Setting::UnverifiedRole => settings.unverified_role = None,
+        Setting::UserRole => settings.user_role = None,
+        Setting::MuteRole => settings.mute_role = None,
+    };
+    store.save().await?;
+    msg.channel_id.say(&ctx, "Setting cleared").await?;
+    Ok(())
+}
+
+/// Embed field values are capped at 1024 characters.
+const MAX_APPEAL_LEN: usize = 1000;
+
+#[command("appeal")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(1)]
+#[usage = "<message|off>"]
+#[example = "Appeal at https://example.com/appeal"]
+#[description(
+    "Set the appeal info added to the DM members get when they are kicked, banned or muted"
+)]
+async fn config_appeal(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = args.rest().trim();
+    if text.chars().count() > MAX_APPEAL_LEN {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!(
+                    "Keep the appeal message under {} characters",
+                    MAX_APPEAL_LEN
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+    let appeal = Some(text.to_string()).filter(|t| !t.eq_ignore_ascii_case("off"));
+    let reply = if appeal.is_some() {
+        "Appeal message set"
+    } else {
+        "Appeal message cleared"
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        store.get_mut(msg.guild_id.unwrap()).appeal_message = appeal;
+        store.save().await?;
+    }
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+const MAX_PREFIXES: usize = 10;
+const MAX_PREFIX_LEN: usize = 25;
+
+#[command("list")]
+#[only_in("guilds")]
+#[description("List the prefixes that work in this server")]
+async fn prefix_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let prefixes = guildconfig::current_prefixes(ctx, msg.guild_id).await;
+    let listed = prefixes
+        .iter()
+        .map(|p| format!("`{}`", p))
+        .collect::<Vec<String>>()
+        .join(", ");
+    msg.channel_id
+        .say(&ctx, format!("Prefixes for this server: {}", listed))
+        .await?;
+    Ok(())
+}
+
+#[command("add")]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+#[usage = "<prefix>"]
+#[example = "\"bot \""]
+#[description("Add a prefix for this server. Quote it to keep a trailing space")]
+async fn prefix_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = args.single_quoted::<String>()?;
+    if prefix.trim().is_empty() || prefix.chars().count() > MAX_PREFIX_LEN {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("A prefix needs between 1 and {} characters", MAX_PREFIX_LEN),
+            )
+            .await?;
+        return Ok(());
+    }
+    let reply = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let settings = store.get_mut(msg.guild_id.unwrap());
+        if settings.prefixes.contains(&prefix) {
+            format!("`{}` is already a prefix", prefix)
+        } else if settings.prefixes.len() >= MAX_PREFIXES {
+            format!("A server can only have {} prefixes", MAX_PREFIXES)
+        } else {
+            settings.prefixes.push(prefix.clone());
+            store.save().await?;
+            format!("Added `{}` as a prefix", prefix)
+        }
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("remove")]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+#[usage = "<prefix>"]
+#[description("Remove a prefix from this server. Removing the last one restores the default")]
+async fn prefix_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = args.single_quoted::<String>()?;
+    let reply = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let settings = store.get_mut(msg.guild_id.unwrap());
+        let before = settings.prefixes.len();
+        settings.prefixes.retain(|p| p != &prefix);
+        if settings.prefixes.len() == before {
+            format!("`{}` is not a prefix here", prefix)
+        } else {
+            store.save().await?;
+            format!("Removed `{}`", prefix)
+        }
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::utils::client;
+use crate::utils::{client, config::BotConfigKey};
 use dagpirs;
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -76,9 +76,9 @@ async fn roast(ctx: &Context, msg: &Message) -> CommandResult {
 async fn joke(ctx: &Context, msg: &Message) -> CommandResult {
     let data = ctx.data.read().await;
     let cliet = data.get::<client::ClientKey>().expect("No Client");
-    let tok = std::env::var("DAGPI_TOKEN").expect("No token");
+    let tok = &data.get::<BotConfigKey>().expect("No Config").dagpi.token;
     let now = Instant::now();
-    let resp = cliet.get("https://api.dagpi.xyz/data/joke", &tok).await;
+    let resp = cliet.get("https://api.dagpi.xyz/data/joke", tok).await;
     let new_now = Instant::now();
     let diff = new_now.duration_since(now);
     match resp {
@@ -199,14 +199,14 @@ async fn pride(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
             .replace("webp", "png");
         let flag = args.single::<String>().unwrap();
         let now = Instant::now();
-        let tok = std::env::var("DAGPI_TOKEN").expect("No token");
+        let tok = &data.get::<BotConfigKey>().expect("No Config").dagpi.token;
         let resp = cliet
             .get(
                 &format!(
                     "https://api.dagpi.xyz/image/pride/?url={}&flag={}",
                     av, flag
                 ),
-                &tok,
+                tok,
             )
             .await;
         let new_now = Instant::now();
@@ -250,11 +250,25 @@ async fn approve(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
     let member = args.single::<id::UserId>().unwrap();
     let cliet = data.get::<client::ClientKey>().expect("No Client");
     let now = Instant::now();
-    let tok = std::env::var("DAGPI_ADMIN").expect("No token");
+    let tok = match &data
+        .get::<BotConfigKey>()
+        .expect("No Config")
+        .dagpi
+        .admin_token
+    {
+        Some(t) => t,
+        None => {
+            typing.stop();
+            msg.channel_id
+                .say(&ctx, "No Dagpi admin token is configured")
+                .await?;
+            return Ok(());
+        }
+    };
     let resp = cliet
         .post(
             &format!("https://central.dagpi.xyz/tokens/{}", member.as_u64()),
-            &tok,
+            tok,
         )
         .await;
     let new_now = Instant::now();
@@ -271,7 +285,7 @@ async fn approve(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
             let app_id = args.single::<String>().unwrap();
             println!("Test, {}", app_id);
             let resp_b = cliet
-                .patch(&format!("https://central.dagpi.xyz/app/{}", app_id), &tok)
+                .patch(&format!("https://central.dagpi.xyz/app/{}", app_id), tok)
                 .await;
             match resp_b {
                 Ok(_r_b) => {
@@ -348,7 +362,21 @@ async fn reject(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let member = args.single::<String>().unwrap();
     let cliet = data.get::<client::ClientKey>().expect("No Client");
     let now = Instant::now();
-    let tok = std::env::var("DAGPI_ADMIN").expect("No token");
+    let tok = match &data
+        .get::<BotConfigKey>()
+        .expect("No Config")
+        .dagpi
+        .admin_token
+    {
+        Some(t) => t,
+        None => {
+            typing.stop();
+            msg.channel_id
+                .say(&ctx, "No Dagpi admin token is configured")
+                .await?;
+            return Ok(());
+        }
+    };
     let reason = args.single_quoted::<String>().unwrap();
     let json = serde_json::json!({
         "uu": member,
@@ -357,7 +385,7 @@ async fn reject(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     .to_string();
     println!("{}", json);
     let resp = cliet
-        .post_body("https://central.dagpi.xyz/app/reject", &tok, json)
+        .post_body("https://central.dagpi.xyz/app/reject", tok, json)
         .await;
     let new_now = Instant::now();
     let diff = new_now.duration_since(now);
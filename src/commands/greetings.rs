@@ -0,0 +1,276 @@
+use crate::utils::{
+    greetings::{self, Delivery, Greeting, Kind, Style, MAX_TEMPLATE_LEN},
+    guildconfig::{self, GuildConfigKey},
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::io;
+
+fn name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Welcome => "welcome",
+        Kind::Farewell => "farewell",
+    }
+}
+
+async fn update(
+    ctx: &Context,
+    guild: GuildId,
+    kind: Kind,
+    change: impl FnOnce(&mut Greeting),
+) -> io::Result<()> {
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.");
+    change(greetings::greeting_mut(store.get_mut(guild), kind));
+    store.save().await
+}
+
+async fn show(ctx: &Context, msg: &Message, kind: Kind) -> CommandResult {
+    let settings = guildconfig::settings(ctx, msg.guild_id.unwrap()).await;
+    let greeting = greetings::greeting(&settings, kind);
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(match kind {
+                    Kind::Welcome => "Welcome Message",
+                    Kind::Farewell => "Farewell Message",
+                });
+                e.description(format!("```\n{}\n```", greeting.template));
+                e.field("Sent To", greeting.delivery, true);
+                e.field("Style", greeting.style, true);
+                e.field(
+                    "Placeholders",
+                    "`{user}` `{mention}` `{guild}` `{member_count}` `{account_age}` `{prefix}` `{channel:rules}` `{channel:verify}` `{channel:welcome}` `{channel:roles}`",
+                    false,
+                );
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+async fn set(ctx: &Context, msg: &Message, args: Args, kind: Kind) -> CommandResult {
+    let template = args.rest().trim().to_string();
+    if template.is_empty() {
+        msg.channel_id
+            .say(&ctx, format!("Usage: `{} set <template>`", name(kind)))
+            .await?;
+        return Ok(());
+    }
+    if template.chars().count() > MAX_TEMPLATE_LEN {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("Templates can be at most {} characters", MAX_TEMPLATE_LEN),
+            )
+            .await?;
+        return Ok(());
+    }
+    update(ctx, msg.guild_id.unwrap(), kind, |g| g.template = template).await?;
+    msg.channel_id
+        .say(
+            &ctx,
+            format!("Template updated. Try it with `{} test`", name(kind)),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn send_to(ctx: &Context, msg: &Message, mut args: Args, kind: Kind) -> CommandResult {
+    let delivery = match args.single::<String>().map(|s| s.parse::<Delivery>()) {
+        Ok(Ok(d)) => d,
+        Ok(Err(why)) => {
+            msg.channel_id.say(&ctx, why).await?;
+            return Ok(());
+        }
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Choose `channel`, `dm`, `both` or `off`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let guild = msg.guild_id.unwrap();
+    update(ctx, guild, kind, |g| g.delivery = delivery).await?;
+    let mut reply = format!("The {} message is sent to: {}", name(kind), delivery);
+    let has_channel = guildconfig::settings(ctx, guild)
+        .await
+        .welcome_channel
+        .is_some();
+    if matches!(delivery, Delivery::Channel | Delivery::Both) && !has_channel {
+        reply.push_str(". Set a welcome channel with `config set welcome` first");
+    }
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+async fn style(ctx: &Context, msg: &Message, mut args: Args, kind: Kind) -> CommandResult {
+    let style = match args.single::<String>().map(|s| s.parse::<Style>()) {
+        Ok(Ok(s)) => s,
+        Ok(Err(why)) => {
+            msg.channel_id.say(&ctx, why).await?;
+            return Ok(());
+        }
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Choose `embed` or `plain`")
+                .await?;
+            return Ok(());
+        }
+    };
+    update(ctx, msg.guild_id.unwrap(), kind, |g| g.style = style).await?;
+    msg.channel_id
+        .say(
+            &ctx,
+            format!("The {} message is sent as: {}", name(kind), style),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn test(ctx: &Context, msg: &Message, kind: Kind) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let greeting = greetings::greeting(&guildconfig::settings(ctx, guild).await, kind);
+    greetings::preview(ctx, guild, &msg.author, &greeting, msg.channel_id).await?;
+    if greeting.delivery == Delivery::Off {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!(
+                    "The {} message is off. Turn it on with `{} send`",
+                    name(kind),
+                    name(kind)
+                ),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn reset(ctx: &Context, msg: &Message, kind: Kind) -> CommandResult {
+    update(ctx, msg.guild_id.unwrap(), kind, |g| {
+        *g = Greeting::default_for(kind)
+    })
+    .await?;
+    msg.channel_id
+        .say(
+            &ctx,
+            format!("The {} message is back to the default", name(kind)),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command("show")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("View the welcome message and the placeholders it can use")]
+async fn welcome_show(ctx: &Context, msg: &Message) -> CommandResult {
+    show(ctx, msg, Kind::Welcome).await
+}
+
+#[command("set")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[usage = "<template>"]
+#[example = "Welcome {mention} to {guild}! You are member #{member_count}. Read {channel:rules}"]
+#[description("Change the welcome message")]
+async fn welcome_set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    set(ctx, msg, args, Kind::Welcome).await
+}
+
+#[command("send")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<channel|dm|both|off>"]
+#[description("Send the welcome message to the welcome channel, by DM, both, or not at all")]
+async fn welcome_send(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_to(ctx, msg, args, Kind::Welcome).await
+}
+
+#[command("style")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<embed|plain>"]
+#[description("Send the welcome message as an embed or plain text")]
+async fn welcome_style(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    style(ctx, msg, args, Kind::Welcome).await
+}
+
+#[command("test")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("Preview the welcome message as if you had just joined")]
+async fn welcome_test(ctx: &Context, msg: &Message) -> CommandResult {
+    test(ctx, msg, Kind::Welcome).await
+}
+
+#[command("reset")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("Go back to the default welcome message")]
+async fn welcome_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    reset(ctx, msg, Kind::Welcome).await
+}
+
+#[command("show")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("View the farewell message and the placeholders it can use")]
+async fn farewell_show(ctx: &Context, msg: &Message) -> CommandResult {
+    show(ctx, msg, Kind::Farewell).await
+}
+
+#[command("set")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[usage = "<template>"]
+#[example = "{user} left {guild}. We're down to {member_count} members"]
+#[description("Change the farewell message")]
+async fn farewell_set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    set(ctx, msg, args, Kind::Farewell).await
+}
+
+#[command("send")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<channel|dm|both|off>"]
+#[description("Send the farewell message to the welcome channel, by DM, both, or not at all. DMs only arrive if the member shares another server with the bot")]
+async fn farewell_send(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    send_to(ctx, msg, args, Kind::Farewell).await
+}
+
+#[command("style")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<embed|plain>"]
+#[description("Send the farewell message as an embed or plain text")]
+async fn farewell_style(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    style(ctx, msg, args, Kind::Farewell).await
+}
+
+#[command("test")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("Preview the farewell message as if you had just left")]
+async fn farewell_test(ctx: &Context, msg: &Message) -> CommandResult {
+    test(ctx, msg, Kind::Farewell).await
+}
+
+#[command("reset")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("Go back to the default farewell message")]
+async fn farewell_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    reset(ctx, msg, Kind::Farewell).await
+}
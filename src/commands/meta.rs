@@ -1,10 +1,11 @@
+use crate::utils::stats::Window;
 use crate::CommandCounter;
 use crate::ShardManagerContainer;
 use crate::{utils::uptimer, EventCounter};
 use prettytable::{Cell, Row, Slice, Table};
 use serenity::builder::{CreateEmbed, CreateEmbedAuthor, CreateMessage};
 use serenity::client::bridge::gateway::ShardId;
-use serenity::framework::standard::{macros::command, CommandResult};
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandResult};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::Colour;
@@ -81,16 +82,32 @@ async fn latency(ctx: &Context, msg: &Message) -> CommandResult {
 // Options are passed via subsequent attributes.
 // Make this command use the "complicated" bucket.
 #[bucket = "complicated"]
-async fn commands(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut contents = "Commands used:\n".to_string();
+#[max_args(1)]
+#[usage = "[24h|7d|all]"]
+#[description("Commands used in this server")]
+async fn commands(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let window = match parse_window(&mut args) {
+        Ok(w) => w,
+        Err(why) => {
+            msg.channel_id.say(&ctx.http, why).await?;
+            return Ok(());
+        }
+    };
+    let mut contents = format!("Commands used ({}):\n", window.label());
 
     let data = ctx.data.read().await;
     let counter = data
         .get::<CommandCounter>()
         .expect("Expected CommandCounter in TypeMap.");
 
-    for (k, v) in counter {
-        writeln!(contents, "- {name}: {amount}", name = k, amount = v)?;
+    for t in counter.tally(msg.guild_id, window) {
+        writeln!(
+            contents,
+            "- {name}: {amount} ({rate})",
+            name = t.name,
+            amount = t.total,
+            rate = t.rate
+        )?;
     }
 
     msg.channel_id.say(&ctx.http, &contents).await?;
@@ -98,19 +115,51 @@ async fn commands(ctx: &Context, msg: &Message) -> CommandResult {
     Ok(())
 }
 
+fn parse_window(args: &mut Args) -> Result<Window, String> {
+    if args.is_empty() {
+        return Ok(Window::Day);
+    }
+    args.single::<Window>().map_err(|e| match e {
+        ArgError::Parse(why) => why,
+        _ => "Use `24h`, `7d` or `all`".to_string(),
+    })
+}
+
 #[command]
 #[bucket = "complicated"]
-async fn socket(ctx: &Context, msg: &Message) -> CommandResult {
-    let data = ctx.data.read().await;
-    let counter = data
-        .get::<EventCounter>()
-        .expect("Expected EVentCounter in TypeMap.");
+#[max_args(1)]
+#[usage = "[24h|7d|all]"]
+#[description("Gateway events received")]
+async fn socket(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let window = match parse_window(&mut args) {
+        Ok(w) => w,
+        Err(why) => {
+            msg.channel_id.say(&ctx.http, why).await?;
+            return Ok(());
+        }
+    };
+    // Collect first so the menu below doesn't hold the TypeMap lock.
+    let tallies = {
+        let data = ctx.data.read().await;
+        data.get::<EventCounter>()
+            .expect("Expected EVentCounter in TypeMap.")
+            .tally(window)
+    };
     let mut table = Table::new();
     let mut vc = Vec::new();
-    table.set_titles(Row::new(vec![Cell::new("Event"), Cell::new("Occurences")]));
-    for (k, v) in counter {
-        table.add_row(Row::new(vec![Cell::new(k), Cell::new(&v.to_string())]));
+    table.set_titles(Row::new(vec![
+        Cell::new("Event"),
+        Cell::new("Occurences"),
+        Cell::new("Rate"),
+    ]));
+    for t in tallies {
+        table.add_row(Row::new(vec![
+            Cell::new(&t.name),
+            Cell::new(&t.total.to_string()),
+            Cell::new(&t.rate),
+        ]));
     }
+    let title = format!("Socket Stats ({})", window.label());
 
     let rows = table.len();
 
@@ -118,7 +167,7 @@ async fn socket(ctx: &Context, msg: &Message) -> CommandResult {
         msg.channel_id
             .send_message(&ctx.http, |f| {
                 f.embed(|em| {
-                    em.title("Socket Stats");
+                    em.title(&title);
                     let st = table.to_string();
                     em.description(format!("```\n{}\n```", st));
                     em
@@ -132,7 +181,7 @@ async fn socket(ctx: &Context, msg: &Message) -> CommandResult {
 
             if i - 10 > rows {
                 v.embed(|em| {
-                    em.title("Socket Stats");
+                    em.title(&title);
                     let st = table.slice(i - 10..rows).to_string();
                     em.description(format!("```\n{}\n```", st));
                     em
@@ -140,7 +189,7 @@ async fn socket(ctx: &Context, msg: &Message) -> CommandResult {
                 break;
             } else {
                 v.embed(|em| {
-                    em.title("Socket Stats");
+                    em.title(&title);
                     let st = table.slice(i - 10..i).to_string();
                     em.description(format!("```\n{}\n```", st));
                     em
@@ -1,7 +1,19 @@
+pub mod antiraid;
+pub mod appeals;
+pub mod automod;
+pub mod cases;
+pub mod channels;
+pub mod config;
 pub mod dagpi;
+pub mod greetings;
 pub mod info;
 pub mod math;
 pub mod meta;
 pub mod moderation;
+pub mod modmail;
 pub mod owner;
+pub mod roles;
 pub mod translation;
+pub mod verification;
+pub mod warnings;
+pub mod wordfilter;
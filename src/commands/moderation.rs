@@ -1,146 +1,390 @@
+use crate::utils::{
+    cases::{self, Case, CaseAction},
+    confirm, guildconfig,
+    moderation::{self, Notice},
+    modlog,
+    reactionroles::ReactionRoleKey,
+    scheduler::{self, Action, SchedulerKey},
+    timeparser::HumanDuration,
+    wordfilter,
+};
 use serenity::builder::CreateEmbed;
 use serenity::framework::standard::{macros::command, Args, CommandResult};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::{Color, MessageBuilder};
 use std::char;
-use std::time::Duration;
-use tokio::time::sleep;
+use tracing::error;
 #[command]
 #[required_permissions("KICK_MEMBERS")]
-#[num_args(2)]
+#[min_args(2)]
 #[only_in("guilds")]
 #[aliases("ki", "yeet")]
-#[usage = "member reason"]
+#[usage = "member reason [--silent]"]
 async fn kick(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let member = args.single::<id::UserId>();
-    match member {
+    let mem = match args.single::<id::UserId>() {
+        Ok(m) => m,
         Err(_) => {
             msg.channel_id
                 .say(&ctx, "Couldn't find the Member ")
-                .await
-                .unwrap();
+                .await?;
+            return Ok(());
         }
-        Ok(mem) => match args.single::<String>() {
-            Err(_) => {
-                msg.channel_id
-                    .say(&ctx, "Kick needs a Valid reason ")
-                    .await
-                    .unwrap();
-            }
-            Ok(res) => match msg.guild_id {
-                None => {
-                    msg.channel_id
-                        .say(&ctx, "Need a Guild to kick mem from")
-                        .await
-                        .unwrap();
-                }
-                Some(g) => {
-                    let member = g.member(&ctx, mem).await.unwrap();
-                    member.kick_with_reason(&ctx, &res).await.unwrap();
-                    msg.channel_id
-                        .say(&ctx, format!("Succesfully Kicked {}", member.user.name))
-                        .await
-                        .unwrap();
-                }
-            },
-        },
     };
+    let (res, silent) = moderation::take_silent(args.rest());
+    if res.is_empty() {
+        msg.channel_id
+            .say(&ctx, "Kick needs a Valid reason ")
+            .await?;
+        return Ok(());
+    }
+    let g = msg.guild_id.unwrap();
+    let user = mem.to_user(ctx).await?;
+    let notice = Notice {
+        action: CaseAction::Kick,
+        reason: Some(res.clone()),
+        duration: None,
+    };
+    let reply =
+        match moderation::check_and_notify(ctx, g, msg.author.id, mem, &notice, silent).await {
+            Err(why) => format!("Couldn't kick {}: {}", user.name, why),
+            Ok(dm) => match moderation::kick(ctx, g, mem, msg.author.id, res).await {
+                Ok(case) => format!(
+                    "Succesfully Kicked {} (case #{}). {}",
+                    user.name,
+                    case.number,
+                    moderation::dm_note(dm)
+                ),
+                Err(why) => format!("Couldn't kick {}: {}", user.name, why),
+            },
+        };
+    msg.channel_id.say(&ctx, reply).await?;
     Ok(())
 }
 
 #[command]
-#[required_permissions("KICK_MEMBERS")]
-#[num_args(3)]
+#[required_permissions("BAN_MEMBERS")]
+#[min_args(2)]
 #[only_in("guilds")]
 #[aliases("b")]
-#[usage = "member reason"]
+#[usage = "member reason [--silent]"]
 async fn ban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let member = args.single::<id::UserId>();
-    match member {
+    let mem = match args.single::<id::UserId>() {
+        Ok(m) => m,
         Err(_) => {
             msg.channel_id
                 .say(&ctx, "Couldn't find the Member ")
-                .await
-                .unwrap();
+                .await?;
+            return Ok(());
         }
-        Ok(mem) => match args.single::<String>() {
-            Err(_) => {
-                msg.channel_id
-                    .say(&ctx, "Mute needs a Valid reason ")
-                    .await
-                    .unwrap();
-            }
-            Ok(res) => match msg.guild_id {
-                None => {
-                    msg.channel_id
-                        .say(&ctx, "Need a Guild to mute from")
-                        .await
-                        .unwrap();
-                }
-                Some(g) => {
-                    let member = g.member(&ctx, mem).await.unwrap();
-                    member.ban_with_reason(&ctx, 0, &res).await.unwrap();
-                    msg.channel_id
-                        .say(&ctx, format!("Succesfully Banned {}", member.user.name))
-                        .await
-                        .unwrap();
-                }
+    };
+    let (res, silent) = moderation::take_silent(args.rest());
+    if res.is_empty() {
+        msg.channel_id
+            .say(&ctx, "Ban needs a Valid reason ")
+            .await?;
+        return Ok(());
+    }
+    let g = msg.guild_id.unwrap();
+    let user = mem.to_user(ctx).await?;
+    let notice = Notice {
+        action: CaseAction::Ban,
+        reason: Some(res.clone()),
+        duration: None,
+    };
+    let reply =
+        match moderation::check_and_notify(ctx, g, msg.author.id, mem, &notice, silent).await {
+            Err(why) => format!("Couldn't ban {}: {}", user.name, why),
+            Ok(dm) => match moderation::ban(ctx, g, mem, msg.author.id, res, 0).await {
+                Ok(case) => format!(
+                    "Succesfully Banned {} (case #{}). {}",
+                    user.name,
+                    case.number,
+                    moderation::dm_note(dm)
+                ),
+                Err(why) => format!("Couldn't ban {}: {}", user.name, why),
             },
-        },
+        };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+/// Days of messages softban and massban delete unless told otherwise.
+const DEFAULT_DELETE_DAYS: u8 = 7;
+/// Most user ids one massban takes.
+const MASSBAN_LIMIT: usize = 200;
+
+fn parse_delete_days(raw: &str) -> Result<u8, String> {
+    match raw.parse::<u8>() {
+        Ok(d) if d <= 7 => Ok(d),
+        _ => Err("Messages can be deleted from the last 0 to 7 days".to_string()),
+    }
+}
+
+#[command]
+#[required_permissions("BAN_MEMBERS")]
+#[min_args(2)]
+#[only_in("guilds")]
+#[usage = "member [--days n] reason [--silent]"]
+#[example = "@spammer --days 3 Posting scam links"]
+#[description("Ban a member to delete their messages from the last few days (7 by default), then unban them right away")]
+async fn softban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mem = match args.single::<id::UserId>() {
+        Ok(m) => m,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Couldn't find the Member ")
+                .await?;
+            return Ok(());
+        }
     };
+    let days = match args.current() {
+        Some(flag) if flag.eq_ignore_ascii_case("--days") => {
+            args.advance();
+            match parse_delete_days(&args.single::<String>().unwrap_or_default()) {
+                Ok(d) => d,
+                Err(why) => {
+                    msg.channel_id.say(&ctx, why).await?;
+                    return Ok(());
+                }
+            }
+        }
+        _ => DEFAULT_DELETE_DAYS,
+    };
+    let (res, silent) = moderation::take_silent(args.rest());
+    if res.is_empty() {
+        msg.channel_id
+            .say(&ctx, "Softban needs a Valid reason ")
+            .await?;
+        return Ok(());
+    }
+    let user = mem.to_user(ctx).await?;
+    let g = msg.guild_id.unwrap();
+    // Don't ask for confirmation of something that would be refused anyway.
+    if let Err(why) =
+        moderation::check_target(ctx, g, msg.author.id, mem, CaseAction::Softban).await
+    {
+        msg.channel_id
+            .say(&ctx, format!("Couldn't softban {}: {}", user.name, why))
+            .await?;
+        return Ok(());
+    }
+    let question = format!(
+        "Softban {} and delete their messages from the last {} days?",
+        user.tag(),
+        days
+    );
+    if !confirm::ask(ctx, msg, question).await? {
+        return Ok(());
+    }
+    let notice = Notice {
+        action: CaseAction::Softban,
+        reason: Some(res.clone()),
+        duration: None,
+    };
+    let reply =
+        match moderation::check_and_notify(ctx, g, msg.author.id, mem, &notice, silent).await {
+            Err(why) => format!("Couldn't softban {}: {}", user.name, why),
+            Ok(dm) => match moderation::softban(ctx, g, mem, msg.author.id, res, days).await {
+                Ok(case) => format!(
+                    "Softbanned {} and deleted {} days of messages (case #{}). {}",
+                    user.name,
+                    days,
+                    case.number,
+                    moderation::dm_note(dm)
+                ),
+                Err(why) => format!("Couldn't softban {}: {}", user.name, why),
+            },
+        };
+    msg.channel_id.say(&ctx, reply).await?;
     Ok(())
 }
 
 #[command]
+#[required_permissions("BAN_MEMBERS")]
+#[min_args(3)]
 #[only_in("guilds")]
-#[usage = "member, duration"]
-async fn verify(ctx: &Context, msg: &Message) -> CommandResult {
-    let member = msg.author.id;
-    println!("Verify");
-    let uuid: u64 = 699134157510672424;
-    if (msg.channel_id.as_u64()) == &uuid {
-        match msg.guild_id {
-            None => {
-                msg.channel_id
-                    .say(&ctx, "Need a Guild to verify")
-                    .await
-                    .unwrap();
+#[usage = "ids... [--days n] --reason reason"]
+#[example = "123456789012345678 234567890123456789 --days 1 --reason Spam bots"]
+#[description("Ban many users by id at once, including users who aren't in the server")]
+async fn massban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut users = Vec::new();
+    let mut invalid = Vec::new();
+    let mut days = DEFAULT_DELETE_DAYS;
+    let mut reason = String::new();
+    while !args.is_empty() {
+        let token = args.single_quoted::<String>().unwrap_or_default();
+        match token.to_lowercase().as_str() {
+            "--reason" => {
+                reason = args.rest().trim().to_string();
+                break;
             }
-            Some(g) => {
-                let mut us = g.member(&ctx, member).await.unwrap();
-                if let Some(guild) = g.to_guild_cached(&ctx).await {
-                    if let Some(role) = guild.role_by_name("User") {
-                        let rid = role.id;
-                        if us.roles.contains(&rid) {
-                            let rs = msg.channel_id.say(&ctx, "Aldready Has Role").await.unwrap();
-                            rs.delete(&ctx).await.unwrap();
-                        } else {
-                            us.add_role(&ctx, rid).await.unwrap();
-                            let rtr = guild.role_by_name("Unverified").unwrap();
-                            us.remove_role(&ctx, rtr.id).await.unwrap();
-                            let rs = msg
-                                .channel_id
-                                .say(&ctx, "You Have been verified")
-                                .await
-                                .unwrap();
-                            rs.delete(&ctx).await.unwrap();
-                        }
-                    }
+            "--days" => match parse_delete_days(&args.single::<String>().unwrap_or_default()) {
+                Ok(d) => days = d,
+                Err(why) => {
+                    msg.channel_id.say(&ctx, why).await?;
+                    return Ok(());
                 }
+            },
+            _ => match token.parse::<UserId>() {
+                Ok(u) if !users.contains(&u) => users.push(u),
+                Ok(_) => {}
+                Err(_) => invalid.push(token),
+            },
+        }
+    }
+    if users.is_empty() || reason.is_empty() {
+        msg.channel_id
+            .say(
+                &ctx,
+                "Usage: `massban <ids...> [--days n] --reason <reason>`",
+            )
+            .await?;
+        return Ok(());
+    }
+    if users.len() > MASSBAN_LIMIT {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("A massban can take at most {} ids", MASSBAN_LIMIT),
+            )
+            .await?;
+        return Ok(());
+    }
+    let question = format!(
+        "Ban {} users and delete their messages from the last {} days?",
+        users.len(),
+        days
+    );
+    if !confirm::ask(ctx, msg, question).await? {
+        return Ok(());
+    }
+
+    let guild = msg.guild_id.unwrap();
+    let total = users.len();
+    let mut progress = msg
+        .channel_id
+        .say(&ctx, format!(":hourglass: Banning 0/{}...", total))
+        .await?;
+    let mut lines = invalid
+        .iter()
+        .map(|t| format!(":x: `{}`: not a user id", t))
+        .collect::<Vec<String>>();
+    let mut banned = 0;
+    for (done, user) in users.into_iter().enumerate() {
+        match moderation::ban(ctx, guild, user, msg.author.id, reason.clone(), days).await {
+            Ok(case) => {
+                banned += 1;
+                lines.push(format!(
+                    ":white_check_mark: `{}`: banned (case #{})",
+                    user.0, case.number
+                ));
             }
+            Err(why) => lines.push(format!(":x: `{}`: {}", user.0, why)),
+        }
+        if (done + 1) % 10 == 0 {
+            let _ = progress
+                .edit(&ctx, |m| {
+                    m.content(format!(":hourglass: Banning {}/{}...", done + 1, total))
+                })
+                .await;
         }
-        msg.delete(&ctx).await.unwrap();
     }
+
+    let failed = lines.len() - banned;
+    let mut summary = String::new();
+    for (shown, line) in lines.iter().enumerate() {
+        if summary.len() + line.len() > 3900 {
+            summary.push_str(&format!("...and {} more", lines.len() - shown));
+            break;
+        }
+        summary.push_str(line);
+        summary.push('\n');
+    }
+    progress
+        .edit(&ctx, |m| {
+            m.content("").embed(|e| {
+                e.title(format!("Massban: {} banned, {} failed", banned, failed));
+                e.description(summary);
+                e.color(if failed == 0 {
+                    Color::DARK_GREEN
+                } else {
+                    Color::ORANGE
+                });
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[required_permissions("BAN_MEMBERS")]
+#[min_args(3)]
+#[only_in("guilds")]
+#[aliases("tb")]
+#[usage = "member duration reason [--silent]"]
+#[example = "@someone 1d spamming"]
+#[description("Ban a member and unban them automatically once the time is up")]
+async fn tempban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mem = match args.single::<id::UserId>() {
+        Ok(m) => m,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Usage: `tempban <member> <duration> <reason>`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let time = match HumanDuration::from_args(&mut args) {
+        Ok(t) => t,
+        Err(why) => {
+            msg.channel_id.say(&ctx, why).await?;
+            return Ok(());
+        }
+    };
+    let (res, silent) = moderation::take_silent(args.rest());
+    if res.trim().is_empty() {
+        msg.channel_id
+            .say(&ctx, "Usage: `tempban <member> <duration> <reason>`")
+            .await?;
+        return Ok(());
+    }
+    let g = msg.guild_id.unwrap();
+    let user = mem.to_user(ctx).await?;
+    let notice = Notice {
+        action: CaseAction::Tempban,
+        reason: Some(res.clone()),
+        duration: Some(time),
+    };
+    let reply = match moderation::check_and_notify(ctx, g, msg.author.id, mem, &notice, silent)
+        .await
+    {
+        Err(why) => format!("Couldn't ban {}: {}", user.name, why),
+        Ok(dm) => {
+            match moderation::tempban(ctx, g, mem, msg.author.id, res, time, msg.channel_id).await {
+                Ok(case) => format!(
+                    "Succesfully Banned {} for {} (until <t:{}:f>, case #{}). {}",
+                    user.name,
+                    time,
+                    case.expires_at.unwrap_or_default(),
+                    case.number,
+                    moderation::dm_note(dm)
+                ),
+                Err(why) => format!("Couldn't ban {}: {}", user.name, why),
+            }
+        }
+    };
+    msg.channel_id.say(&ctx, reply).await?;
     Ok(())
 }
 
 #[command]
 #[required_permissions("KICK_MEMBERS")]
-#[num_args(2)]
+#[min_args(2)]
 #[only_in("guilds")]
 #[aliases("mu")]
-#[usage = "member, duration"]
+#[usage = "member duration [reason] [--silent]"]
 async fn mute(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let member = args.single::<id::UserId>();
     match member {
@@ -158,34 +402,161 @@ async fn mute(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                     .unwrap();
             }
             Some(g) => {
-                let mut us = g.member(&ctx, mem).await.unwrap();
-                if let Some(guild) = g.to_guild_cached(&ctx).await {
-                    if let Some(role) = guild.role_by_name("Mute") {
-                        let rid = role.id;
-                        if us.roles.contains(&rid) {
-                            msg.channel_id.say(&ctx, "Aldready Has Role").await.unwrap();
-                        } else {
-                            us.add_role(&ctx, rid).await.unwrap();
-                            msg.channel_id.say(&ctx, "Muted the User").await.unwrap();
-                            let time = args.single::<u64>().unwrap();
-                            sleep(Duration::from_secs(time)).await;
-                            us.remove_role(&ctx, rid).await.unwrap();
+                let time = match HumanDuration::from_args(&mut args) {
+                    Ok(t) => t,
+                    Err(why) => {
+                        msg.channel_id.say(&ctx, why).await?;
+                        return Ok(());
+                    }
+                };
+                if guildconfig::settings(ctx, g).await.mute_role.is_none() {
+                    msg.channel_id
+                        .say(&ctx, moderation::ActionError::NoMuteRole)
+                        .await?;
+                    return Ok(());
+                }
+                let (reason, silent) = moderation::take_silent(args.rest());
+                let reason = Some(reason).filter(|r| !r.is_empty());
+                let notice = Notice {
+                    action: CaseAction::Mute,
+                    reason: reason.clone(),
+                    duration: Some(time),
+                };
+                let dm =
+                    match moderation::check_and_notify(ctx, g, msg.author.id, mem, &notice, silent)
+                        .await
+                    {
+                        Ok(dm) => dm,
+                        Err(why) => {
+                            msg.channel_id.say(&ctx, why).await?;
+                            return Ok(());
                         }
+                    };
+                match moderation::mute(ctx, g, mem, msg.author.id, reason, time).await {
+                    Ok(case) => {
+                        msg.channel_id
+                            .say(
+                                &ctx,
+                                format!(
+                                    "Muted the User for {} (until <t:{}:f>, case #{}). {}",
+                                    time,
+                                    case.expires_at.unwrap_or_default(),
+                                    case.number,
+                                    moderation::dm_note(dm)
+                                ),
+                            )
+                            .await
+                            .unwrap();
+                    }
+                    Err(why) => {
+                        msg.channel_id.say(&ctx, why).await.unwrap();
                     }
                 }
             }
         },
     };
+    Ok(())
+}
 
+#[command]
+#[required_permissions("KICK_MEMBERS")]
+#[num_args(1)]
+#[only_in("guilds")]
+#[aliases("unmu")]
+#[usage = "member"]
+async fn unmute(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mem = match args.single::<id::UserId>() {
+        Ok(m) => m,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Couldn't find the Member ")
+                .await
+                .unwrap();
+            return Ok(());
+        }
+    };
+    let g = msg.guild_id.unwrap();
+    let pending = {
+        let mut data = ctx.data.write().await;
+        let schedule = data
+            .get_mut::<SchedulerKey>()
+            .expect("Expected Scheduler in TypeMap.");
+        let pending = schedule.take_mute(g, mem);
+        if pending.is_some() {
+            schedule.save().await?;
+        }
+        pending
+    };
+    // Prefer the role the mute was applied with, in case the config changed since.
+    let role = match pending.map(|p| p.action) {
+        Some(Action::Unmute { role }) => Some(role),
+        _ => guildconfig::settings(ctx, g).await.mute_role,
+    };
+    let mut us = g.member(&ctx, mem).await?;
+    match role {
+        Some(rid) if us.roles.contains(&rid) => {
+            us.remove_role(&ctx, rid).await?;
+            let case = Case::new(CaseAction::Unmute, mem, msg.author.id, None);
+            let case = cases::open(&ctx.http, &ctx.data, g, case).await;
+            msg.channel_id
+                .say(
+                    &ctx,
+                    format!("Unmuted {} (case #{})", us.user.name, case.number),
+                )
+                .await?;
+        }
+        _ => {
+            msg.channel_id
+                .say(&ctx, format!("{} is not muted", us.user.name))
+                .await?;
+        }
+    }
     Ok(())
 }
 
 #[command]
 #[required_permissions("KICK_MEMBERS")]
-#[num_args(2)]
+#[only_in("guilds")]
+#[description("List active mutes")]
+async fn mutes(ctx: &Context, msg: &Message) -> CommandResult {
+    let lines = {
+        let data = ctx.data.read().await;
+        data.get::<SchedulerKey>()
+            .expect("Expected Scheduler in TypeMap.")
+            .mutes_in(msg.guild_id.unwrap())
+            .iter()
+            .map(|m| {
+                format!(
+                    "<@{}> until <t:{}:f> (<t:{}:R>)",
+                    m.user.0, m.expires_at, m.expires_at
+                )
+            })
+            .collect::<Vec<String>>()
+    };
+    let description = if lines.is_empty() {
+        "Nobody is muted".to_string()
+    } else {
+        lines.join("\n")
+    };
+    msg.channel_id
+        .send_message(&ctx, |f| {
+            f.embed(|e| {
+                e.title("Active Mutes");
+                e.description(description);
+                e.color(Color::ORANGE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[required_permissions("BAN_MEMBERS")]
+#[min_args(1)]
 #[only_in("guilds")]
 #[aliases("ub")]
-#[usage = "member"]
+#[usage = "member [reason]"]
 async fn unban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let member = args.single::<id::UserId>();
     match member {
@@ -195,59 +566,71 @@ async fn unban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 .await
                 .unwrap();
         }
-        Ok(mem) => {
-            let mut banned = false;
-            match msg.guild_id {
-                None => {
-                    msg.channel_id
-                        .say(&ctx, "Need a Guild to kick mem from")
-                        .await
-                        .unwrap();
-                }
-                Some(g) => {
-                    let us = mem.to_user(&ctx).await.unwrap();
-                    let _bad = g
-                        .bans(&ctx)
-                        .await
-                        .unwrap()
-                        .iter()
-                        .map(|ban| {
-                            if ban.user.id == us.clone().id {
-                                banned = true;
-                            }
-                            true
-                        })
-                        .collect::<Vec<bool>>();
-                    if banned {
-                        g.unban(&ctx, us.id).await.unwrap();
+        Ok(mem) => match msg.guild_id {
+            None => {
+                msg.channel_id
+                    .say(&ctx, "Need a Guild to kick mem from")
+                    .await
+                    .unwrap();
+            }
+            Some(g) => {
+                let us = match mem.to_user(&ctx).await {
+                    Ok(u) => u,
+                    Err(_) => {
                         msg.channel_id
-                            .say(&ctx, format!("Succesfully Unbanned {}", us.name))
+                            .say(&ctx, "Couldn't find a user with that id")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let reason = Some(args.rest().trim().to_string()).filter(|r| !r.is_empty());
+                match moderation::unban(ctx, g, us.id, msg.author.id, reason).await {
+                    Ok(Some(case)) => {
+                        msg.channel_id
+                            .say(
+                                &ctx,
+                                format!("Succesfully Unbanned {} (case #{})", us.name, case.number),
+                            )
                             .await
                             .unwrap();
-                    } else {
+                    }
+                    Ok(None) => {
                         msg.channel_id
                             .say(&ctx, format!("{} was not Banned. Didn;t unban", us.name))
                             .await
                             .unwrap();
                     }
+                    Err(why) => {
+                        msg.channel_id.say(&ctx, why.to_string()).await?;
+                    }
                 }
             }
-        }
+        },
     };
     Ok(())
 }
 
 #[command]
-#[required_permissions("KICK_MEMBERS")]
+#[required_permissions("MANAGE_ROLES")]
 #[num_args(6)]
+#[only_in("guilds")]
 #[usage("<@Role> <r> <g> <b> <emoji> <title>")]
+#[description("Post a reaction role embed and register it")]
 async fn role_embed(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let role = args.single::<RoleId>().unwrap();
     let r = args.single::<u8>().unwrap();
     let g = args.single::<u8>().unwrap();
     let b = args.single::<u8>().unwrap();
-    let emoji = args.single::<char>().unwrap();
+    let emoji = args.single::<ReactionType>().unwrap();
     let tit = args.single_quoted::<String>().unwrap();
+    if let Err(why) =
+        moderation::check_assignable(ctx, msg.guild_id.unwrap(), msg.author.id, &[role]).await
+    {
+        msg.channel_id
+            .say(&ctx, format!("Couldn't bind that role: {}", why))
+            .await?;
+        return Ok(());
+    }
     let mut embed = CreateEmbed::default();
     embed.color(Color::from_rgb(r, g, b));
     embed.title(tit);
@@ -267,57 +650,212 @@ async fn role_embed(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
         })
         .await
         .unwrap();
-    m.react(&ctx, ReactionType::Unicode(emoji.to_string()))
-        .await
-        .unwrap();
+    m.react(&ctx, emoji.clone()).await.unwrap();
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<ReactionRoleKey>()
+        .expect("Expected ReactionRoles in TypeMap.");
+    store.bind(msg.guild_id.unwrap(), m.channel_id, m.id, emoji, role);
+    store.save().await?;
     Ok(())
 }
 
+/// Most messages one purge deletes.
+const PURGE_LIMIT: usize = 1000;
+/// Most messages one purge looks through to find matches.
+const PURGE_SCAN_LIMIT: usize = 5000;
+/// Bulk delete refuses messages older than 14 days. A minute of margin keeps
+/// messages from aging out between the scan and the delete.
+const BULK_DELETE_AGE: i64 = 14 * 24 * 60 * 60 - 60;
+
+/// Which messages a purge deletes. Every given filter has to match.
+#[derive(Default)]
+struct PurgeFilter {
+    users: Vec<UserId>,
+    bots: bool,
+    contains: Option<String>,
+    regex: Option<regex::Regex>,
+    attachments: bool,
+    embeds: bool,
+    before: Option<MessageId>,
+    after: Option<MessageId>,
+}
+
+impl PurgeFilter {
+    fn parse(args: &mut Args) -> Result<PurgeFilter, String> {
+        let mut filter = PurgeFilter::default();
+        while !args.is_empty() {
+            let flag = args.single_quoted::<String>().unwrap_or_default();
+            match flag.to_lowercase().as_str() {
+                "--user" => filter.users.push(
+                    args.single::<UserId>()
+                        .map_err(|_| "`--user` needs a member")?,
+                ),
+                "--bots" => filter.bots = true,
+                "--contains" => {
+                    let text = args
+                        .single_quoted::<String>()
+                        .map_err(|_| "`--contains` needs some text")?;
+                    filter.contains = Some(text.to_lowercase());
+                }
+                "--regex" => {
+                    let pattern = args
+                        .single_quoted::<String>()
+                        .map_err(|_| "`--regex` needs a pattern")?;
+                    filter.regex = Some(
+                        wordfilter::compile_pattern(&pattern)
+                            .map_err(|why| format!("That pattern doesn't work: {}", why))?,
+                    );
+                }
+                "--attachments" => filter.attachments = true,
+                "--embeds" => filter.embeds = true,
+                "--before" => filter.before = Some(MessageId(
+                    args.single::<u64>()
+                        .map_err(|_| "`--before` needs a message id")?,
+                )),
+                "--after" => filter.after = Some(MessageId(
+                    args.single::<u64>()
+                        .map_err(|_| "`--after` needs a message id")?,
+                )),
+                _ => return Err(format!(
+                    "`{}` is not a purge filter. Use `--user`, `--bots`, `--contains`, `--regex`, `--attachments`, `--embeds`, `--before` or `--after`",
+                    flag
+                )),
+            }
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, message: &Message) -> bool {
+        (self.users.is_empty() || self.users.contains(&message.author.id))
+            && (!self.bots || message.author.bot)
+            && self
+                .contains
+                .as_ref()
+                .map_or(true, |text| message.content.to_lowercase().contains(text))
+            && self
+                .regex
+                .as_ref()
+                .map_or(true, |regex| regex.is_match(&message.content))
+            && (!self.attachments || !message.attachments.is_empty())
+            && (!self.embeds || !message.embeds.is_empty())
+    }
+}
+
 #[command]
 #[required_permissions("MANAGE_MESSAGES")]
-#[num_args(1)]
+#[min_args(1)]
 #[only_in("guilds")]
 #[aliases("prune", "clear")]
-#[usage = "amount"]
+#[usage = "amount [--user @member] [--bots] [--contains text] [--regex pattern] [--attachments] [--embeds] [--before id] [--after id]"]
+#[example = "50 --user @someone"]
+#[example = "200 --contains \"free nitro\""]
+#[description("Delete recent messages, optionally only the ones matching every filter given. Messages older than 14 days are skipped")]
 async fn purge(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let delete_num = args.single::<u64>();
-    match delete_num {
-        Err(_) => {
+    let amount = match args.single::<usize>() {
+        Ok(n) if n > 0 && n <= PURGE_LIMIT => n,
+        _ => {
             msg.channel_id
                 .say(
                     ctx,
-                    ":no_entry_sign: The value provided was not a valid number",
+                    format!(
+                        ":no_entry_sign: The amount must be a number from 1 to {}",
+                        PURGE_LIMIT
+                    ),
                 )
                 .await?;
+            return Ok(());
         }
-        Ok(delete_n) => {
-            let mut find_msg = msg
-                .channel_id
-                .say(
-                    ctx,
-                    format!(":hourglass: Finding and deleting {} messages...", delete_n),
-                )
-                .await?;
-
-            let channel = &msg.channel(ctx).await.unwrap().guild().unwrap();
-
-            let messages = &channel
-                .messages(ctx, |r| r.before(&msg.id).limit(delete_n))
+    };
+    let filter = match PurgeFilter::parse(&mut args) {
+        Ok(f) => f,
+        Err(why) => {
+            msg.channel_id
+                .say(ctx, format!(":no_entry_sign: {}", why))
                 .await?;
-            let message_ids = messages.iter().map(|m| m.id).collect::<Vec<MessageId>>();
+            return Ok(());
+        }
+    };
+    let mut find_msg = msg
+        .channel_id
+        .say(
+            ctx,
+            format!(":hourglass: Finding and deleting {} messages...", amount),
+        )
+        .await?;
 
-            channel.delete_messages(ctx, message_ids).await?;
+    let channel = &msg.channel(ctx).await.unwrap().guild().unwrap();
+    let oldest_bulk = scheduler::now() - BULK_DELETE_AGE;
+    let mut matched = Vec::new();
+    let mut skipped = 0;
+    let mut scanned = 0;
+    let mut cursor = filter.before.unwrap_or(msg.id);
+    'scan: while matched.len() + skipped < amount && scanned < PURGE_SCAN_LIMIT {
+        let page = channel
+            .messages(ctx, |r| r.before(cursor).limit(100))
+            .await?;
+        if page.is_empty() {
+            break;
+        }
+        scanned += page.len();
+        for message in &page {
+            if filter.after.map_or(false, |after| message.id <= after) {
+                break 'scan;
+            }
+            if !filter.matches(message) {
+                continue;
+            }
+            if scheduler::created_at(message.id.0) < oldest_bulk {
+                skipped += 1;
+            } else {
+                matched.push(message.id);
+            }
+            if matched.len() + skipped >= amount {
+                break 'scan;
+            }
+        }
+        cursor = page.last().unwrap().id;
+    }
 
-            find_msg
-                .edit(ctx, |m| {
-                    m.content(format!(":white_check_mark: Deleted {} messages", delete_n));
-                    m
-                })
-                .await?;
-            msg.delete(&ctx).await.unwrap();
-            find_msg.delete(&ctx).await.unwrap();
+    let mut deleted = 0;
+    let mut failed = 0;
+    for batch in matched.chunks(100) {
+        match channel.delete_messages(ctx, batch).await {
+            Ok(_) => deleted += batch.len(),
+            Err(why) => {
+                error!("Failed to bulk delete in {}: {:?}", channel.id, why);
+                failed += batch.len();
+            }
         }
     }
+    if deleted > 0 {
+        modlog::post_purge(
+            &ctx.http,
+            &ctx.data,
+            channel.guild_id,
+            msg.author.id,
+            channel.id,
+            deleted,
+        )
+        .await;
+    }
+
+    let mut report = format!(":white_check_mark: Deleted {} messages", deleted);
+    if skipped > 0 {
+        report.push_str(&format!(", skipped {} older than 14 days", skipped));
+    }
+    if failed > 0 {
+        report.push_str(&format!(", {} failed", failed));
+    }
+    find_msg
+        .edit(ctx, |m| {
+            m.content(report);
+            m
+        })
+        .await?;
+    let _ = msg.delete(&ctx).await;
+    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    let _ = find_msg.delete(&ctx).await;
 
     Ok(())
 }
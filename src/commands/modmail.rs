@@ -0,0 +1,81 @@
+use crate::utils::modmail::{self, ModmailKey};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+/// Tells the invoker and returns false when the command wasn't used in a
+/// modmail thread.
+async fn in_thread(ctx: &Context, msg: &Message) -> CommandResult<bool> {
+    let is_thread = {
+        let data = ctx.data.read().await;
+        data.get::<ModmailKey>()
+            .expect("Expected Modmail in TypeMap.")
+            .is_thread(msg.guild_id.unwrap(), msg.channel_id)
+    };
+    if !is_thread {
+        msg.channel_id
+            .say(&ctx, "This isn't a modmail thread")
+            .await?;
+    }
+    Ok(is_thread)
+}
+
+#[command]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_MESSAGES")]
+#[usage = "<message>"]
+#[example = "Thanks for letting us know, we're looking into it"]
+#[description("Answer the member in a modmail thread. Attachments are sent along")]
+async fn reply(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if !in_thread(ctx, msg).await? {
+        return Ok(());
+    }
+    let content = args.rest().trim();
+    if content.is_empty() && msg.attachments.is_empty() {
+        msg.channel_id
+            .say(&ctx, "Give a message or an attachment to send")
+            .await?;
+        return Ok(());
+    }
+    match modmail::reply(ctx, msg, content).await {
+        None => {
+            msg.channel_id
+                .say(&ctx, "This isn't a modmail thread")
+                .await?;
+        }
+        Some(true) => {
+            msg.react(&ctx, ReactionType::Unicode("✅".to_string()))
+                .await?;
+        }
+        Some(false) => {
+            msg.channel_id
+                .say(
+                    &ctx,
+                    "Couldn't DM them. They may have DMs closed or left the server",
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_MESSAGES")]
+#[usage = "[reason]"]
+#[description("Close a modmail thread. The transcript is saved and the channel deleted")]
+async fn close(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if !in_thread(ctx, msg).await? {
+        return Ok(());
+    }
+    let reason = Some(args.rest().trim()).filter(|r| !r.is_empty());
+    if modmail::close(ctx, msg.channel_id, msg.author.id, reason)
+        .await?
+        .is_none()
+    {
+        msg.channel_id
+            .say(&ctx, "This isn't a modmail thread")
+            .await?;
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::utils::stats;
 use crate::ShardManagerContainer;
 use serenity::framework::standard::{macros::command, CommandResult};
 use serenity::model::prelude::*;
@@ -6,6 +7,7 @@ use serenity::prelude::*;
 #[command]
 #[owners_only]
 async fn quit(ctx: &Context, msg: &Message) -> CommandResult {
+    stats::flush(&ctx.data).await;
     let data = ctx.data.read().await;
 
     if let Some(manager) = data.get::<ShardManagerContainer>() {
@@ -0,0 +1,333 @@
+use crate::utils::{
+    guildconfig, moderation,
+    reactionroles::ReactionRoleKey,
+    rolemenus::{self, RoleMenu, RoleMenuKey},
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::interactions::message_component::ButtonStyle;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::fmt::Write;
+
+/// Looks for a message in the invoking channel, then in the role channel.
+async fn find_message(ctx: &Context, msg: &Message, id: MessageId) -> Option<Message> {
+    if let Ok(m) = msg.channel_id.message(&ctx.http, id).await {
+        return Some(m);
+    }
+    let role_channel = guildconfig::settings(ctx, msg.guild_id?)
+        .await
+        .role_channel?;
+    role_channel.message(&ctx.http, id).await.ok()
+}
+
+#[command("add")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_ROLES")]
+#[num_args(3)]
+#[usage = "<message id> <emoji> <@role>"]
+#[description("Give a role to members who react to a message with an emoji")]
+async fn reactionrole_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (message_id, emoji, role) = match (
+        args.single::<u64>().map(MessageId),
+        args.single::<ReactionType>(),
+        args.single::<RoleId>(),
+    ) {
+        (Ok(m), Ok(e), Ok(r)) => (m, e, r),
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx,
+                    "Usage: `reactionrole add <message id> <emoji> <@role>`",
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    if let Err(why) =
+        moderation::check_assignable(ctx, msg.guild_id.unwrap(), msg.author.id, &[role]).await
+    {
+        msg.channel_id
+            .say(&ctx, format!("Couldn't bind that role: {}", why))
+            .await?;
+        return Ok(());
+    }
+    let target = match find_message(ctx, msg, message_id).await {
+        Some(m) => m,
+        None => {
+            msg.channel_id
+                .say(
+                    &ctx,
+                    "Couldn't find that message here or in the role channel",
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    if let Err(why) = target.react(&ctx, emoji.clone()).await {
+        msg.channel_id
+            .say(&ctx, format!("Couldn't react with that emoji: {}", why))
+            .await?;
+        return Ok(());
+    }
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<ReactionRoleKey>()
+            .expect("Expected ReactionRoles in TypeMap.");
+        store.bind(
+            msg.guild_id.unwrap(),
+            target.channel_id,
+            target.id,
+            emoji.clone(),
+            role,
+        );
+        store.save().await?;
+    }
+    msg.channel_id
+        .say(
+            &ctx,
+            format!("Reacting with {} now gives <@&{}>", emoji, role.0),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command("remove")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_ROLES")]
+#[num_args(2)]
+#[usage = "<message id> <emoji>"]
+#[description("Stop an emoji on a message from giving a role")]
+async fn reactionrole_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (message_id, emoji) = match (
+        args.single::<u64>().map(MessageId),
+        args.single::<ReactionType>(),
+    ) {
+        (Ok(m), Ok(e)) => (m, e),
+        _ => {
+            msg.channel_id
+                .say(&ctx, "Usage: `reactionrole remove <message id> <emoji>`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let channel = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<ReactionRoleKey>()
+            .expect("Expected ReactionRoles in TypeMap.");
+        let channel = store
+            .menus_in(msg.guild_id.unwrap())
+            .into_iter()
+            .find(|(id, _)| *id == message_id)
+            .map(|(_, m)| m.channel);
+        if channel.is_some() && store.unbind(message_id, &emoji) {
+            store.save().await?;
+            channel
+        } else {
+            None
+        }
+    };
+    match channel {
+        Some(c) => {
+            let _ = c.delete_reaction(&ctx.http, message_id, None, emoji).await;
+            msg.channel_id.say(&ctx, "Reaction role removed").await?;
+        }
+        None => {
+            msg.channel_id
+                .say(&ctx, "That emoji isn't bound on that message")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[command("list")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_ROLES")]
+#[description("List the reaction roles in this server")]
+async fn reactionrole_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let mut text = String::new();
+    {
+        let data = ctx.data.read().await;
+        let store = data
+            .get::<ReactionRoleKey>()
+            .expect("Expected ReactionRoles in TypeMap.");
+        for (id, menu) in store.menus_in(guild) {
+            writeln!(
+                text,
+                "[Message](https://discord.com/channels/{}/{}/{})",
+                guild.0, menu.channel.0, id.0
+            )?;
+            for b in &menu.bindings {
+                writeln!(text, "{} → <@&{}>", b.emoji, b.role.0)?;
+            }
+        }
+    }
+    if text.is_empty() {
+        text.push_str("No reaction roles yet. Add one with `reactionrole add`");
+    }
+    msg.channel_id
+        .send_message(&ctx.http, |f| {
+            f.embed(|e| {
+                e.title("Reaction Roles");
+                e.description(text);
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+struct MenuEntry {
+    role: RoleId,
+    label: String,
+    emoji: Option<ReactionType>,
+}
+
+/// Parses `@role|label|emoji`, where the label and emoji are optional.
+async fn parse_entry(ctx: &Context, raw: &str) -> Option<MenuEntry> {
+    let mut parts = raw.split('|').map(str::trim);
+    let role = parts.next()?.parse::<RoleId>().ok()?;
+    let label = match parts.next().filter(|l| !l.is_empty()) {
+        Some(l) => l.to_string(),
+        None => role.to_role_cached(&ctx.cache).await?.name,
+    };
+    let emoji = match parts.next().filter(|e| !e.is_empty()) {
+        Some(e) => Some(e.parse::<ReactionType>().ok()?),
+        None => None,
+    };
+    Some(MenuEntry { role, label, emoji })
+}
+
+#[command]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_ROLES")]
+#[min_args(3)]
+#[usage = "<buttons|select> <title> <@role|label|emoji>..."]
+#[example = "buttons \"Pick your roles\" \"@Beta|Beta Tester|🧪\" @Pingable"]
+#[description("Post a role picker using buttons or a select menu")]
+async fn rolemenu(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let kind = args.single::<String>()?.to_lowercase();
+    if kind != "buttons" && kind != "select" {
+        msg.channel_id
+            .say(&ctx, "The menu type must be `buttons` or `select`")
+            .await?;
+        return Ok(());
+    }
+    let title = args.single_quoted::<String>()?;
+    let mut entries = Vec::new();
+    for raw in args.iter::<String>().quoted() {
+        let raw = raw?;
+        match parse_entry(ctx, &raw).await {
+            Some(e) => entries.push(e),
+            None => {
+                msg.channel_id
+                    .say(&ctx, format!("Couldn't understand `{}`", raw))
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+    if entries.is_empty() || entries.len() > 25 {
+        msg.channel_id
+            .say(&ctx, "A role menu needs between 1 and 25 roles")
+            .await?;
+        return Ok(());
+    }
+    let roles = entries.iter().map(|e| e.role).collect::<Vec<RoleId>>();
+    if let Err(why) =
+        moderation::check_assignable(ctx, msg.guild_id.unwrap(), msg.author.id, &roles).await
+    {
+        msg.channel_id
+            .say(&ctx, format!("Couldn't make that menu: {}", why))
+            .await?;
+        return Ok(());
+    }
+
+    // The invoking message's id is unique, so it doubles as the menu id.
+    let menu_id = msg.id.0;
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(&title);
+                e.description(
+                    entries
+                        .iter()
+                        .map(|en| match &en.emoji {
+                            Some(emoji) => format!("{} <@&{}>", emoji, en.role.0),
+                            None => format!("<@&{}>", en.role.0),
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                );
+                e.color(Colour::BLURPLE);
+                e
+            });
+            m.components(|c| {
+                if kind == "buttons" {
+                    for row in entries.chunks(5) {
+                        c.create_action_row(|r| {
+                            for en in row {
+                                r.create_button(|b| {
+                                    b.style(ButtonStyle::Secondary).label(&en.label).custom_id(
+                                        format!(
+                                            "{}{}:{}",
+                                            rolemenus::ID_PREFIX,
+                                            menu_id,
+                                            en.role.0
+                                        ),
+                                    );
+                                    if let Some(emoji) = &en.emoji {
+                                        b.emoji(emoji.clone());
+                                    }
+                                    b
+                                });
+                            }
+                            r
+                        });
+                    }
+                } else {
+                    c.create_action_row(|r| {
+                        r.create_select_menu(|s| {
+                            s.custom_id(format!("{}{}", rolemenus::ID_PREFIX, menu_id))
+                                .placeholder("Choose your roles")
+                                .min_values(0)
+                                .max_values(entries.len() as u64)
+                                .options(|o| {
+                                    for en in &entries {
+                                        o.create_option(|opt| {
+                                            opt.label(&en.label).value(en.role.0);
+                                            if let Some(emoji) = &en.emoji {
+                                                opt.emoji(emoji.clone());
+                                            }
+                                            opt
+                                        });
+                                    }
+                                    o
+                                })
+                        })
+                    });
+                }
+                c
+            })
+        })
+        .await?;
+
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<RoleMenuKey>()
+        .expect("Expected RoleMenus in TypeMap.");
+    store.insert(
+        menu_id,
+        RoleMenu {
+            guild: msg.guild_id.unwrap(),
+            roles,
+        },
+    );
+    store.save().await?;
+    Ok(())
+}
@@ -0,0 +1,264 @@
+use crate::utils::{
+    guildconfig::{self, GuildConfigKey},
+    timeparser::HumanDuration,
+    verification::{self, VerifiedKey},
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::interactions::message_component::ButtonStyle;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::time::Duration;
+
+/// Replies in the verify channel are removed after this long.
+const REPLY_LIFETIME: Duration = Duration::from_secs(5);
+/// Longest wait before unverified members are kicked, 30 days.
+const MAX_KICK_AFTER: i64 = 30 * 24 * 60 * 60;
+/// How many verifications `verification log` lists.
+const RECENT_VERIFICATIONS: usize = 15;
+
+async fn say_briefly(ctx: &Context, channel: ChannelId, text: &str) -> CommandResult {
+    let reply = channel.say(&ctx, text).await?;
+    tokio::time::sleep(REPLY_LIFETIME).await;
+    let _ = reply.delete(&ctx).await;
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+#[description("Verify yourself after reading the rules")]
+async fn verify(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let settings = guildconfig::settings(ctx, guild).await;
+    if settings
+        .verify_channel
+        .map_or(false, |c| c != msg.channel_id)
+    {
+        return Ok(());
+    }
+    let _ = msg.delete(&ctx).await;
+    let reply = verification::begin(ctx, guild, msg.author.id).await?;
+    say_briefly(ctx, msg.channel_id, reply).await
+}
+
+#[command("status")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("View how members verify")]
+async fn verification_status(ctx: &Context, msg: &Message) -> CommandResult {
+    let settings = guildconfig::settings(ctx, msg.guild_id.unwrap()).await;
+    let channel = settings
+        .verify_channel
+        .map_or_else(|| "Any channel".to_string(), |c| format!("<#{}>", c.0));
+    let roles = match (settings.unverified_role, settings.user_role) {
+        (_, None) => "No user role is set, so nobody can verify".to_string(),
+        (Some(u), Some(v)) => format!("<@&{}> is swapped for <@&{}>", u.0, v.0),
+        (None, Some(v)) => format!("Members are given <@&{}>", v.0),
+    };
+    let kick = settings.verification.kick_after.map_or_else(
+        || "Off".to_string(),
+        |s| format!("After {}", HumanDuration(time::Duration::seconds(s))),
+    );
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification");
+                e.field("Channel", channel, true);
+                e.field(
+                    "Challenge",
+                    if settings.verification.challenge {
+                        "Arithmetic question by DM"
+                    } else {
+                        "Off"
+                    },
+                    true,
+                );
+                e.field("Kick Unverified", kick, true);
+                e.field("Roles", roles, false);
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command("challenge")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<on|off>"]
+#[description("Ask members an arithmetic question by DM before verifying them")]
+async fn verification_challenge(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let on = match args.single::<String>()?.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            msg.channel_id.say(&ctx, "Choose `on` or `off`").await?;
+            return Ok(());
+        }
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        store.get_mut(msg.guild_id.unwrap()).verification.challenge = on;
+        store.save().await?;
+    }
+    let reply = if on {
+        "Members now answer a question by DM to verify"
+    } else {
+        "Members are verified right away"
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("timeout")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<duration|off>"]
+#[example = "1d"]
+#[description("Kick members who haven't verified this long after joining, up to 30 days")]
+async fn verification_timeout(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let raw = args.single::<String>()?;
+    let seconds = if raw.eq_ignore_ascii_case("off") {
+        None
+    } else {
+        match raw.parse::<HumanDuration>() {
+            Ok(d) if d.whole_seconds() <= MAX_KICK_AFTER => Some(d.whole_seconds()),
+            Ok(_) => {
+                msg.channel_id
+                    .say(&ctx, "The timeout can be at most 30 days")
+                    .await?;
+                return Ok(());
+            }
+            Err(why) => {
+                msg.channel_id.say(&ctx, why.to_string()).await?;
+                return Ok(());
+            }
+        }
+    };
+    let has_user_role = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let settings = store.get_mut(msg.guild_id.unwrap());
+        settings.verification.kick_after = seconds;
+        let has_user_role = settings.user_role.is_some();
+        store.save().await?;
+        has_user_role
+    };
+    let reply = match seconds {
+        None => "Unverified members are no longer kicked".to_string(),
+        Some(s) => format!(
+            "Members who join from now on are kicked if they haven't verified after {}{}",
+            HumanDuration(time::Duration::seconds(s)),
+            if has_user_role {
+                ""
+            } else {
+                ". Set a user role with `config set user` first"
+            }
+        ),
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("button")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[max_args(1)]
+#[usage = "[#channel]"]
+#[description(
+    "Post a button members press to accept the rules and verify. It keeps working across restarts"
+)]
+async fn verification_button(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let settings = guildconfig::settings(ctx, guild).await;
+    let channel = match args.single::<ChannelId>() {
+        Ok(c) => c,
+        Err(_) => settings.verify_channel.unwrap_or(msg.channel_id),
+    };
+    let rules = settings.rules_channel.map_or_else(
+        || "the rules".to_string(),
+        |c| format!("the rules in <#{}>", c.0),
+    );
+    channel
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Welcome!");
+                e.description(format!(
+                    "Read {}, then press the button below to get access to the server",
+                    rules
+                ));
+                e.color(Colour::DARK_GREEN);
+                e
+            })
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.style(ButtonStyle::Success)
+                            .label("I have read the rules")
+                            .custom_id(format!("{}accept", verification::ID_PREFIX))
+                    })
+                })
+            })
+        })
+        .await?;
+    if channel != msg.channel_id {
+        msg.channel_id
+            .say(&ctx, format!("Posted the rules button in <#{}>", channel.0))
+            .await?;
+    }
+    Ok(())
+}
+
+#[command("log")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[max_args(1)]
+#[usage = "[@member]"]
+#[description("See when a member verified, or the latest verifications")]
+async fn verification_log(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let member = args.single::<UserId>().ok();
+    let description = {
+        let data = ctx.data.read().await;
+        let log = data
+            .get::<VerifiedKey>()
+            .expect("Expected Verified in TypeMap.");
+        match member {
+            Some(user) => match log.get(guild, user) {
+                Some(at) => format!("<@{}> verified <t:{}:f> (<t:{}:R>)", user.0, at, at),
+                None => format!("<@{}> hasn't verified since this was tracked", user.0),
+            },
+            None => {
+                let recent = log.recent(guild, RECENT_VERIFICATIONS);
+                if recent.is_empty() {
+                    "Nobody has verified yet".to_string()
+                } else {
+                    recent
+                        .iter()
+                        .map(|(user, at)| format!("<@{}> <t:{}:R>", user.0, at))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }
+            }
+        }
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verifications");
+                e.description(description);
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use crate::utils::{
+    cases::MAX_REASON_LEN,
+    guildconfig::GuildConfigKey,
+    timeparser::HumanDuration,
+    warnings::{self, Punishment, WarnRule, WarningKey},
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+
+#[command]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[min_args(2)]
+#[usage = "<@user> <reason>"]
+#[description("Warn a member. Enough warnings trigger the server's escalation rules")]
+async fn warn(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match args.single::<UserId>() {
+        Ok(u) => u,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Usage: `warn <@user> <reason>`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let guild = msg.guild_id.unwrap();
+    let reason = args.rest().trim().to_string();
+    if reason.chars().count() > MAX_REASON_LEN {
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("Reasons can be at most {} characters", MAX_REASON_LEN),
+            )
+            .await?;
+        return Ok(());
+    }
+    let (case, total, escalation) =
+        match warnings::warn(ctx, guild, user, msg.author.id, reason, msg.channel_id).await {
+            Ok(warned) => warned,
+            Err(why) => {
+                msg.channel_id
+                    .say(&ctx, format!("Couldn't warn <@{}>: {}", user.0, why))
+                    .await?;
+                return Ok(());
+            }
+        };
+    let mut reply = format!(
+        "Warned <@{}> (case #{}). They now have {} warning{}",
+        user.0,
+        case.number,
+        total,
+        if total == 1 { "" } else { "s" }
+    );
+    match escalation {
+        Ok(Some((rule, escalated))) => reply.push_str(&format!(
+            "\nReached {}, applied case #{}",
+            rule, escalated.number
+        )),
+        Ok(None) => {}
+        Err(why) => reply.push_str(&format!("\nCouldn't apply the escalation: {}", why)),
+    }
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("warnings")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[num_args(1)]
+#[usage = "<@user>"]
+#[description("List a member's warnings")]
+async fn warn_list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match args.single::<UserId>() {
+        Ok(u) => u,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Usage: `warnings <@user>`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let lines = {
+        let data = ctx.data.read().await;
+        data.get::<WarningKey>()
+            .expect("Expected Warnings in TypeMap.")
+            .list(msg.guild_id.unwrap(), user)
+            .iter()
+            .map(|w| {
+                format!(
+                    "`#{}` <t:{}:d> by <@{}>: {}",
+                    w.case, w.created_at, w.moderator.0, w.reason
+                )
+            })
+            .collect::<Vec<String>>()
+    };
+    let description = if lines.is_empty() {
+        format!("<@{}> has no warnings", user.0)
+    } else {
+        lines.join("\n")
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("Warnings ({})", lines.len()));
+                e.description(description);
+                e.color(Colour::GOLD);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[min_args(1)]
+#[max_args(2)]
+#[usage = "<@user> [case number]"]
+#[description("Clear all of a member's warnings, or a single one")]
+async fn clearwarn(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match args.single::<UserId>() {
+        Ok(u) => u,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Usage: `clearwarn <@user> [case number]`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let case = if args.is_empty() {
+        None
+    } else {
+        match args.single::<u64>() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                msg.channel_id
+                    .say(&ctx, "The case number must be a number")
+                    .await?;
+                return Ok(());
+            }
+        }
+    };
+    let removed = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<WarningKey>()
+            .expect("Expected Warnings in TypeMap.");
+        let removed = store.clear(msg.guild_id.unwrap(), user, case);
+        if removed > 0 {
+            store.save().await?;
+        }
+        removed
+    };
+    let reply = match (removed, case) {
+        (0, Some(n)) => format!("<@{}> has no warning from case #{}", user.0, n),
+        (0, None) => format!("<@{}> has no warnings", user.0),
+        (_, Some(n)) => format!("Cleared the warning from case #{}", n),
+        (n, None) => format!("Cleared {} warnings from <@{}>", n, user.0),
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("add")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(2)]
+#[usage = "<warnings> [within] <mute <duration>|kick|ban [duration]>"]
+#[example = "3 7d mute 1h"]
+#[example = "5 kick"]
+#[description("Punish members automatically once they reach a number of warnings")]
+async fn warnrule_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let count = match args.single::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            msg.channel_id
+                .say(&ctx, "The warning count must be a number above zero")
+                .await?;
+            return Ok(());
+        }
+    };
+    let within = args
+        .single::<HumanDuration>()
+        .ok()
+        .map(|d| d.whole_seconds());
+    let punishment = match args.rest().parse::<Punishment>() {
+        Ok(p) => p,
+        Err(why) => {
+            msg.channel_id.say(&ctx, why).await?;
+            return Ok(());
+        }
+    };
+    let rule = WarnRule {
+        warnings: count,
+        within,
+        punishment,
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let rules = &mut store.get_mut(msg.guild_id.unwrap()).warn_rules;
+        rules.retain(|r| r.warnings != count);
+        rules.push(rule);
+        rules.sort_by_key(|r| r.warnings);
+        store.save().await?;
+    }
+    msg.channel_id
+        .say(&ctx, format!("Added rule: {}", rule))
+        .await?;
+    Ok(())
+}
+
+#[command("remove")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[num_args(1)]
+#[usage = "<warnings>"]
+#[description("Remove the escalation rule for a warning count")]
+async fn warnrule_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let count = match args.single::<usize>() {
+        Ok(n) => n,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx, "Usage: `warnrule remove <warnings>`")
+                .await?;
+            return Ok(());
+        }
+    };
+    let removed = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let rules = &mut store.get_mut(msg.guild_id.unwrap()).warn_rules;
+        let before = rules.len();
+        rules.retain(|r| r.warnings != count);
+        let removed = before != rules.len();
+        if removed {
+            store.save().await?;
+        }
+        removed
+    };
+    let reply = if removed {
+        format!("Removed the rule for {} warnings", count)
+    } else {
+        format!("There is no rule for {} warnings", count)
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("list")]
+#[only_in("guilds")]
+#[required_permissions("KICK_MEMBERS")]
+#[description("List this server's warning escalation rules")]
+async fn warnrule_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let rules = {
+        let data = ctx.data.read().await;
+        data.get::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.")
+            .get(msg.guild_id.unwrap())
+            .warn_rules
+    };
+    let description = if rules.is_empty() {
+        "No escalation rules yet. Add one with `warnrule add`".to_string()
+    } else {
+        rules
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Warning Escalation");
+                e.description(description);
+                e.color(Colour::GOLD);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use crate::utils::{
+    guildconfig::GuildConfigKey,
+    wordfilter::{self, FilterSettings, WordFilterKey},
+};
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+
+/// Applies `change` to the guild's filter lists, saves them and drops the
+/// compiled copy so the next message rebuilds it.
+async fn update<T>(
+    ctx: &Context,
+    guild: GuildId,
+    change: impl FnOnce(&mut FilterSettings) -> T,
+) -> Result<T, std::io::Error> {
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.");
+    let changed = change(&mut store.get_mut(guild).filter);
+    store.save().await?;
+    data.get_mut::<WordFilterKey>()
+        .expect("Expected WordFilter in TypeMap.")
+        .invalidate(guild);
+    Ok(changed)
+}
+
+#[command("add")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(1)]
+#[usage = "<word or phrase>"]
+#[description("Block a word. Matching ignores case, accents and look-alike letters")]
+async fn filter_add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let word = wordfilter::normalize(args.rest().trim());
+    if word.trim().is_empty() {
+        msg.channel_id
+            .say(&ctx, "That word is empty once normalized")
+            .await?;
+        return Ok(());
+    }
+    let added = update(ctx, msg.guild_id.unwrap(), |f| {
+        if f.words.contains(&word) {
+            false
+        } else {
+            f.words.push(word.clone());
+            true
+        }
+    })
+    .await?;
+    let reply = if added {
+        format!("Blocked `{}`", word)
+    } else {
+        format!("`{}` is already blocked", word)
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("regex")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(1)]
+#[usage = "<pattern>"]
+#[example = r"fr[e3]{2}\s*nitro"]
+#[description(
+    "Block a regular expression. It is matched case-insensitively against normalized text"
+)]
+async fn filter_regex(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let pattern = args.rest().trim().to_string();
+    if let Err(why) = wordfilter::compile_pattern(&pattern) {
+        msg.channel_id
+            .say(&ctx, format!("That pattern doesn't work: {}", why))
+            .await?;
+        return Ok(());
+    }
+    let added = update(ctx, msg.guild_id.unwrap(), |f| {
+        if f.patterns.contains(&pattern) {
+            false
+        } else {
+            f.patterns.push(pattern.clone());
+            true
+        }
+    })
+    .await?;
+    let reply = if added {
+        format!("Blocked the pattern `{}`", pattern)
+    } else {
+        format!("`{}` is already blocked", pattern)
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("remove")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[min_args(1)]
+#[usage = "<word or pattern>"]
+#[description("Unblock a word or pattern")]
+async fn filter_remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let entry = args.rest().trim().to_string();
+    let word = wordfilter::normalize(&entry);
+    let removed = update(ctx, msg.guild_id.unwrap(), |f| {
+        let before = f.words.len() + f.patterns.len();
+        f.words.retain(|w| *w != word);
+        f.patterns.retain(|p| *p != entry);
+        before != f.words.len() + f.patterns.len()
+    })
+    .await?;
+    let reply = if removed {
+        format!("Unblocked `{}`", entry)
+    } else {
+        format!("`{}` isn't blocked", entry)
+    };
+    msg.channel_id.say(&ctx, reply).await?;
+    Ok(())
+}
+
+#[command("list")]
+#[only_in("guilds")]
+#[required_permissions("MANAGE_GUILD")]
+#[description("List this server's blocked words and patterns")]
+async fn filter_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let filter = {
+        let data = ctx.data.read().await;
+        data.get::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.")
+            .get(msg.guild_id.unwrap())
+            .filter
+    };
+    let show = |entries: &[String]| {
+        if entries.is_empty() {
+            "None".to_string()
+        } else {
+            entries
+                .iter()
+                .map(|e| format!("`{}`", e))
+                .collect::<Vec<String>>()
+                .join(", ")
+                .chars()
+                .take(1000)
+                .collect()
+        }
+    };
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Word Filter");
+                e.field("Words", show(&filter.words), false);
+                e.field("Patterns", show(&filter.patterns), false);
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
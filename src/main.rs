@@ -6,6 +6,7 @@ use unidecode::unidecode;
 
 use dagpirs;
 use sentry;
+use serenity::model::event::Event;
 use serenity::{
     async_trait,
     client::bridge::gateway::GatewayIntents,
@@ -21,11 +22,9 @@ use serenity::{
     model::{
         channel::Message,
         channel::Reaction,
-        channel::ReactionType,
         event::ResumedEvent,
         gateway::Ready,
         guild::Member,
-        id::ChannelId,
         id::GuildId,
         id::UserId,
         interactions::{application_command::ApplicationCommand, Interaction},
@@ -35,23 +34,39 @@ use serenity::{
         prelude::User,
     },
     prelude::*,
-    utils::Colour,
-};
-use serenity::{model::event::Event, utils::MessageBuilder};
-use std::{
-    collections::{HashMap, HashSet},
-    env,
-    sync::Arc,
 };
+use std::{collections::HashSet, sync::Arc};
 use utils::{
+    antiraid::{self, JoinTracker, RaidKey},
+    appeals,
+    automod::{self, AutomodKey, RepeatTracker},
+    cases::{CaseAction, CaseKey, CaseStore},
     client,
+    config::{BotConfig, BotConfigKey},
+    confirm::{self, ConfirmKey, Confirmations},
+    greetings::{self, Kind},
+    guildconfig::{self, GuildConfigKey, GuildConfigStore},
+    locks::{LockKey, LockStore},
+    modlog,
+    modmail::{self, ModmailKey, ModmailStore},
+    reactionroles::{ReactionRoleKey, ReactionRoleStore},
+    rolemenus::{self, RoleMenuKey, RoleMenuStore},
+    scheduler::{self, Schedule, SchedulerKey},
+    stats::{self, CommandStats, EventStats},
     uptimer::{Uptimer, UptimerKey},
+    verification::{self, ChallengeKey, Challenges, VerifiedKey, VerifiedStore},
+    warnings::{WarningKey, WarningStore},
+    wordfilter::{self, FilterCache, WordFilterKey},
 };
 
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-use commands::{dagpi::*, info::*, math::*, meta::*, moderation::*, owner::*, translation::*};
+use commands::{
+    antiraid::*, appeals::*, automod::*, cases::*, channels::*, config::*, dagpi::*, greetings::*,
+    info::*, math::*, meta::*, moderation::*, modmail::*, owner::*, roles::*, translation::*,
+    verification::*, warnings::*, wordfilter::*,
+};
 
 pub struct ShardManagerContainer;
 
@@ -70,8 +85,7 @@ impl RawEventHandler for RawHandler {
             .get_mut::<EventCounter>()
             .expect("Expected CommandCounter in TypeMap.");
         let et = format!("{:?}", ev.event_type());
-        let entry = counter.entry(et).or_insert(0);
-        *entry += 1;
+        counter.record(et);
     }
 }
 
@@ -121,175 +135,202 @@ impl EventHandler for Handler {
         info!("Resumed");
     }
 
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.guild_id.is_none() {
+            if !verification::check_answer(&ctx, &msg).await {
+                modmail::relay_dm(&ctx, &msg).await;
+            }
+            return;
+        }
+        if !wordfilter::check_message(&ctx, &msg).await {
+            automod::check(&ctx, &msg).await;
+        }
+    }
+
+    async fn guild_member_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Member>,
+        new: Member,
+    ) {
+        wordfilter::check_nickname(&ctx, &new).await;
+    }
+
+    async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, banned_user: User) {
+        modlog::record_external(&ctx, guild_id, CaseAction::Ban, banned_user.id).await;
+    }
+
+    async fn guild_ban_removal(&self, ctx: Context, guild_id: GuildId, unbanned_user: User) {
+        modlog::record_external(&ctx, guild_id, CaseAction::Unban, unbanned_user.id).await;
+    }
+
     async fn guild_member_removal(
         &self,
         ctx: Context,
-        _guild_id: GuildId,
+        guild_id: GuildId,
         user: User,
         _member_data_if_available: Option<Member>,
     ) {
-        let o = user.direct_message(&ctx.http, |f| {
-            f.embed(|e| {
-                e.title("Sorry you had to leave");
-                e.description(format!(
-                    "Permarent Server Invite: [Invite](https://server.daggy.tech)\nServer Owner: `Daggy#1234`"
-                ));
-                e.color(Colour::RED);
-                e
-            })
-        })
-        .await;
-
-        match o {
-            Ok(_m) => {}
-            Err(_e) => {}
-        };
+        greetings::deliver(&ctx, guild_id, &user, Kind::Farewell).await;
+        // Most leaves aren't kicks, so the audit log lookup shouldn't hold
+        // anything up.
+        tokio::spawn(async move {
+            modlog::record_external(&ctx, guild_id, CaseAction::Kick, user.id).await;
+        });
     }
 
     async fn guild_member_addition(&self, ctx: Context, guild: GuildId, mut mem: Member) {
-        let cached_guild = guild.to_guild_cached(&ctx.cache).await.unwrap();
-        let gid: u64 = 491175207122370581;
-        if cached_guild.id.as_u64() == &gid {
-            let channel = cached_guild
-                .channel_id_from_name(&ctx.cache, "welcomes")
-                .await
-                .unwrap();
-            let ch = cached_guild
-                .channel_id_from_name(&ctx.cache, "rules")
-                .await
-                .unwrap();
-            let role = cached_guild.role_by_name("Unverified").unwrap();
-            mem.add_role(&ctx.http, role).await.unwrap();
-            let msg = MessageBuilder::new().push("Welcome").mention(&mem.user).push_bold(" to Daggy Tech!").push(" A Server that houses projects like Dagpi,Dagbot,R.Daggy, Polraorid and More.\nTo Verify Head on over to").channel(ch).push(" , read the rules and say `daggy verify` to verify!\nHave a Great Time!").build();
-            channel.say(&ctx.http, msg).await.unwrap();
-
-            mem.user
-                .direct_message(&ctx.http, |f| {
-                    f.embed(|e| {
-                        e.title("Welcome to Daggy Tech!");
-                        e.description("Please be sure to read the rules in the rules channel and verify by sending `daggy verify`.\nDo remember to keep topics in their relevant channels.\nHave a safe and fun time.\nPing the mods to report a problem.");
-                        e.color(Colour::DARK_GREEN);
-                        e
-                    })
-                })
-                .await
-                .expect("No Dm");
-            let decoded = unidecode(&mem.user.name);
-            if decoded != mem.user.name {
-                mem.edit(&ctx.http, |f| f.nickname(decoded))
-                    .await
-                    .expect("Unable to edit");
+        if antiraid::on_join(&ctx, guild, &mem).await {
+            return;
+        }
+        let settings = guildconfig::settings(&ctx, guild).await;
+        if let Some(role) = settings.unverified_role {
+            if let Err(why) = mem.add_role(&ctx.http, role).await {
+                error!(
+                    "Failed to give {} the unverified role: {:?}",
+                    mem.user.id, why
+                );
+            }
+        }
+        verification::on_join(&ctx, guild, &mem).await;
+        greetings::deliver(&ctx, guild, &mem.user, Kind::Welcome).await;
+        let decoded = unidecode(&mem.user.name);
+        if decoded != mem.user.name {
+            if let Err(why) = mem.edit(&ctx.http, |f| f.nickname(decoded)).await {
+                error!("Failed to decode the name of {}: {:?}", mem.user.id, why);
             }
         }
     }
 
-    #[allow(unused_variables)]
     async fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
-        let role_channel = ChannelId::from(783311887323889675);
-
-        if role_channel == reaction.channel_id {
-            let user = reaction.user_id.unwrap();
-            let cached_guild = reaction
-                .guild_id
-                .unwrap()
-                .to_guild_cached(&ctx.cache)
-                .await
-                .unwrap();
+        let (guild_id, user) = match (reaction.guild_id, reaction.user_id) {
+            (Some(g), Some(u)) => (g, u),
+            _ => return,
+        };
+        let role = {
+            let data = ctx.data.read().await;
+            data.get::<ReactionRoleKey>()
+                .expect("Expected ReactionRoles in TypeMap.")
+                .role_for(reaction.message_id, &reaction.emoji)
+        };
 
-            let role: (&str, u64) = match reaction.emoji.clone() {
-                ReactionType::Custom { animated, id, name } => ("None", 217462890364403712),
-                ReactionType::Unicode(estr) => match estr.as_str() {
-                    "\u{01f9ea}" => ("Beta-Test", 824569176198086726),
-                    "\u{002705}" => ("Pingable", 824569150168104970),
-                    "\u{01f44c}" => ("Dagpi Notifs", 824569314169978900),
-                    "\u{01f4f7}" => ("Polaroid Updates", 824569274823081994),
-                    "\u{01f916}" => ("R.Daggy", 824569350609436672),
-                    "\u{01f3a8}" => ("DagCord", 824569681343807519),
-                    _ => ("None", 217462890364403712),
-                },
-                _ => ("None", 217462890364403712),
-            };
-            if role.0 != "None" && &role.1 == reaction.message_id.as_u64() {
-                let mut mem = cached_guild.member(&ctx, user).await.unwrap();
-                let beta_role = cached_guild.role_by_name(role.0).unwrap();
-                if mem.roles.contains(&beta_role.id) {
-                    match mem.remove_role(&ctx, beta_role).await {
-                        Ok(_o) => {
-                            match mem
-                                .user
-                                .direct_message(&ctx, |f| {
-                                    f.content(format!("Removed role `{}`", role.0))
-                                })
-                                .await
-                            {
-                                Ok(_o) => {}
-                                Err(_e) => {}
-                            };
-                        }
-                        Err(_e) => println!("COuldn't add role"),
-                    };
-                } else {
-                    println!("No role")
+        if let Some(role) = role {
+            let mut mem = match guild_id.member(&ctx, user).await {
+                Ok(m) => m,
+                Err(why) => {
+                    error!("Failed to fetch reacting member {}: {:?}", user, why);
+                    return;
                 }
+            };
+            let role_name = role
+                .to_role_cached(&ctx.cache)
+                .await
+                .map(|r| r.name)
+                .unwrap_or_else(|| role.0.to_string());
+            if mem.roles.contains(&role) {
+                match mem.remove_role(&ctx, role).await {
+                    Ok(_o) => {
+                        match mem
+                            .user
+                            .direct_message(&ctx, |f| {
+                                f.content(format!("Removed role `{}`", role_name))
+                            })
+                            .await
+                        {
+                            Ok(_o) => {}
+                            Err(_e) => {}
+                        };
+                    }
+                    Err(why) => error!("Failed to take {} from {}: {:?}", role, user, why),
+                };
+            } else {
+                warn!("{} unreacted for {} without having it", user, role);
             }
         }
     }
 
-    #[allow(unused_variables)]
     async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
-        let role_channel = ChannelId::from(783311887323889675);
-
-        if role_channel == reaction.channel_id {
-            let user = reaction.user_id.unwrap();
-            let cached_guild = reaction
-                .guild_id
-                .unwrap()
-                .to_guild_cached(&ctx.cache)
-                .await
-                .unwrap();
+        let (guild_id, user) = match (reaction.guild_id, reaction.user_id) {
+            (Some(g), Some(u)) => (g, u),
+            _ => return,
+        };
+        if user == ctx.cache.current_user_id().await {
+            return;
+        }
+        let (role, is_menu) = {
+            let data = ctx.data.read().await;
+            let store = data
+                .get::<ReactionRoleKey>()
+                .expect("Expected ReactionRoles in TypeMap.");
+            (
+                store.role_for(reaction.message_id, &reaction.emoji),
+                store.is_menu(reaction.message_id),
+            )
+        };
 
-            let role: (&str, u64) = match reaction.emoji.clone() {
-                ReactionType::Custom { animated, id, name } => ("None", 217462890364403712),
-                ReactionType::Unicode(estr) => match estr.as_str() {
-                    "\u{01f9ea}" => ("Beta-Test", 824569176198086726),
-                    "\u{002705}" => ("Pingable", 824569150168104970),
-                    "\u{01f44c}" => ("Dagpi Notifs", 824569314169978900),
-                    "\u{01f4f7}" => ("Polaroid Updates", 824569274823081994),
-                    "\u{01f916}" => ("R.Daggy", 824569350609436672),
-                    "\u{01f3a8}" => ("DagCord", 824569681343807519),
-                    _ => ("None", 217462890364403712),
-                },
-                _ => ("None", 217462890364403712),
-            };
-            if role.0 != "None" && &role.1 == reaction.message_id.as_u64() {
-                let mut mem = cached_guild.member(&ctx, user).await.unwrap();
-                let beta_role = cached_guild.role_by_name(role.0).unwrap();
-                if mem.roles.contains(&beta_role.id) {
-                    println!("Reapply")
-                } else {
-                    match mem.add_role(&ctx, beta_role).await {
-                        Ok(_o) => {
-                            match mem
-                                .user
-                                .direct_message(&ctx, |f| {
-                                    f.content(format!("Gave you the role `{}`", role.0))
-                                })
-                                .await
-                            {
-                                Ok(_o) => {}
-                                Err(_e) => {}
-                            };
-                        }
-                        Err(_e) => println!("COuldn't add role"),
-                    };
+        if let Some(role) = role {
+            let mut mem = match guild_id.member(&ctx, user).await {
+                Ok(m) => m,
+                Err(why) => {
+                    error!("Failed to fetch reacting member {}: {:?}", user, why);
+                    return;
                 }
+            };
+            let role_name = role
+                .to_role_cached(&ctx.cache)
+                .await
+                .map(|r| r.name)
+                .unwrap_or_else(|| role.0.to_string());
+            if mem.roles.contains(&role) {
+                warn!("{} reacted for {} but already has it", user, role);
             } else {
-                reaction.delete(&ctx).await.unwrap();
+                match mem.add_role(&ctx, role).await {
+                    Ok(_o) => {
+                        match mem
+                            .user
+                            .direct_message(&ctx, |f| {
+                                f.content(format!("Gave you the role `{}`", role_name))
+                            })
+                            .await
+                        {
+                            Ok(_o) => {}
+                            Err(_e) => {}
+                        };
+                    }
+                    Err(why) => error!("Failed to give {} to {}: {:?}", role, user, why),
+                };
             }
-        };
+        } else {
+            // Keep menus and the role channel free of reactions that do nothing.
+            let role_channel = guildconfig::settings(&ctx, guild_id).await.role_channel;
+            if is_menu || role_channel == Some(reaction.channel_id) {
+                if let Err(why) = reaction.delete(&ctx).await {
+                    error!(
+                        "Failed to remove a reaction in {}: {:?}",
+                        reaction.channel_id, why
+                    );
+                }
+            }
+        }
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::MessageComponent(component) = &interaction {
+            if component.data.custom_id.starts_with(rolemenus::ID_PREFIX) {
+                rolemenus::handle_component(&ctx, component).await;
+            } else if component.data.custom_id.starts_with(confirm::ID_PREFIX) {
+                confirm::handle_component(&ctx, component).await;
+            } else if component.data.custom_id.starts_with(appeals::ID_PREFIX) {
+                appeals::handle_component(&ctx, component).await;
+            } else if component
+                .data
+                .custom_id
+                .starts_with(verification::ID_PREFIX)
+            {
+                verification::handle_component(&ctx, component).await;
+            }
+            return;
+        }
         if let Interaction::ApplicationCommand(cmd) = interaction {
             if cmd.data.name == "echo".to_string() {
                 cmd.create_interaction_response(&ctx.http, |f| {
@@ -390,9 +431,140 @@ struct Fun;
 
 #[group]
 #[description("Top class moderation suite")]
-#[commands(purge, kick, ban, unban, mute, verify, role_embed)]
+#[commands(
+    purge,
+    lock,
+    unlock,
+    lockdown,
+    slowmode,
+    kick,
+    ban,
+    tempban,
+    softban,
+    massban,
+    unban,
+    mute,
+    unmute,
+    mutes,
+    case_show,
+    case_list,
+    case_reason,
+    warn,
+    warn_list,
+    clearwarn,
+    verify,
+    role_embed,
+    rolemenu
+)]
 struct Moderation;
 
+#[group]
+#[description("Per server channels and roles")]
+#[prefix = "config"]
+#[default_command(config_show)]
+#[commands(config_show, config_set, config_unset, config_appeal)]
+struct Config;
+
+#[group]
+#[description("Automatic message filters")]
+#[prefix = "automod"]
+#[default_command(automod_show)]
+#[commands(
+    automod_show,
+    automod_on,
+    automod_off,
+    automod_rule,
+    automod_exempt,
+    automod_unexempt
+)]
+struct Automod;
+
+#[group]
+#[description("Blocked words and patterns")]
+#[prefix = "filter"]
+#[default_command(filter_list)]
+#[commands(filter_list, filter_add, filter_regex, filter_remove)]
+struct WordFilter;
+
+#[group]
+#[description("Join raid detection and raid mode")]
+#[prefix = "raid"]
+#[default_command(raid_status)]
+#[commands(raid_status, raid_setup, raid_on, raid_off)]
+struct Raid;
+
+#[group]
+#[description("Appeal a ban in DMs")]
+#[commands(appeal)]
+struct Appeals;
+
+#[group]
+#[description("How new members verify")]
+#[prefix = "verification"]
+#[default_command(verification_status)]
+#[commands(
+    verification_status,
+    verification_challenge,
+    verification_timeout,
+    verification_button,
+    verification_log
+)]
+struct Verification;
+
+#[group]
+#[description("The message new members get")]
+#[prefix = "welcome"]
+#[default_command(welcome_show)]
+#[commands(
+    welcome_show,
+    welcome_set,
+    welcome_send,
+    welcome_style,
+    welcome_test,
+    welcome_reset
+)]
+struct Welcome;
+
+#[group]
+#[description("The message sent when members leave")]
+#[prefix = "farewell"]
+#[default_command(farewell_show)]
+#[commands(
+    farewell_show,
+    farewell_set,
+    farewell_send,
+    farewell_style,
+    farewell_test,
+    farewell_reset
+)]
+struct Farewell;
+
+#[group]
+#[description("Answer members who DM the bot")]
+#[commands(reply, close)]
+struct Modmail;
+
+#[group]
+#[description("Automatic punishments for repeated warnings")]
+#[prefix = "warnrule"]
+#[default_command(warnrule_list)]
+#[commands(warnrule_list, warnrule_add, warnrule_remove)]
+struct WarnRules;
+
+#[group]
+#[description("Custom prefixes for this server")]
+#[prefix = "prefix"]
+#[default_command(prefix_list)]
+#[commands(prefix_list, prefix_add, prefix_remove)]
+struct Prefix;
+
+#[group]
+#[description("Roles members pick for themselves by reacting")]
+#[prefixes("reactionrole", "rr")]
+#[default_command(reactionrole_list)]
+#[commands(reactionrole_add, reactionrole_remove, reactionrole_list)]
+struct ReactionRoles;
+
 #[group]
 #[description("Commands for stalking!")]
 #[commands(serverinfo, userinfo, commands, spotify, ide)]
@@ -406,19 +578,20 @@ struct Text;
 pub struct CommandCounter;
 
 impl TypeMapKey for CommandCounter {
-    type Value = HashMap<String, u64>;
+    type Value = CommandStats;
 }
 
 pub struct EventCounter;
 
 impl TypeMapKey for EventCounter {
-    type Value = HashMap<String, usize>;
+    type Value = EventStats;
 }
 
 #[help]
 // This replaces the information that a user can pass
 // a command-name as argument to gain specific information about it.
-#[individual_command_tip = "```diff\n- Use `daggy help <command>` for help with a command\n+ Use `daggy help <catgeory>` for help with a category\n```"]
+// `{prefix}` is filled in with the guild's prefix by `misc::help::with_embeds`.
+#[individual_command_tip = "```diff\n- Use `{prefix}help <command>` for help with a command\n+ Use `{prefix}help <catgeory>` for help with a category\n```"]
 // Some arguments require a `{}` in order to replace it with contextual information.
 // In this case our `{}` refers to a command's name.
 #[command_not_found_text = "Could not find: `{}`."]
@@ -455,16 +628,26 @@ async fn my_help(
     Ok(())
 }
 #[hook]
-async fn before(ctx: &Context, _msg: &Message, command_name: &str) -> bool {
-    // Increment the number of times this command has been run once. If
-    // the command's name does not exist in the counter, add a default
-    // value of 0.
+async fn dynamic_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    // The framework only asks for one prefix, so hand back whichever of the
+    // guild's prefixes this message actually starts with.
+    let prefixes = guildconfig::current_prefixes(ctx, msg.guild_id).await;
+    prefixes
+        .iter()
+        .find(|p| msg.content.starts_with(p.as_str()))
+        .or_else(|| prefixes.first())
+        .cloned()
+}
+
+#[hook]
+async fn before(ctx: &Context, msg: &Message, command_name: &str) -> bool {
+    // Count this use in the current hour and day for the guild. The counter
+    // is written to disk by the flush task started in `main`.
     let mut data = ctx.data.write().await;
     let counter = data
         .get_mut::<CommandCounter>()
         .expect("Expected CommandCounter in TypeMap.");
-    let entry = counter.entry(command_name.to_string()).or_insert(0);
-    *entry += 1;
+    counter.record(msg.guild_id, command_name);
 
     true // if `before` returns false, command processing doesn't happen.
 }
@@ -570,17 +753,34 @@ async fn main() {
     let subscriber = FmtSubscriber::builder()
         .with_env_filter(EnvFilter::from_default_env())
         .finish();
-    let _guard = sentry::init(env::var("SENTRY").expect("Expected a token in the environment"));
-    sentry::capture_message("Bot online", sentry::Level::Info);
-
     tracing::subscriber::set_global_default(subscriber).expect("Failed to start the logger");
 
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
+    // Settings come from `config.toml` (or `DAGGY_CONFIG`), with environment
+    // variables taking precedence. See `config.example.toml`.
+    let config = match BotConfig::load() {
+        Ok(c) => Arc::new(c),
+        Err(why) => {
+            error!("Invalid configuration: {}", why);
+            std::process::exit(1);
+        }
+    };
+
+    // Sentry stays off unless a DSN is configured.
+    let _sentry = config.sentry.dsn.as_deref().map(|dsn| {
+        let guard = sentry::init(dsn);
+        sentry::capture_message("Bot online", sentry::Level::Info);
+        guard
+    });
+    if config.sentry.dsn.is_none() {
+        info!("No Sentry DSN configured, error reporting is disabled");
+    }
+
+    let token = &config.discord.token;
 
-    let http = Http::new_with_token(&token);
+    let http = Http::new_with_token(token);
 
     // We will fetch your bot's owners and id
-    let (owners, _bot_id) = match http.get_current_application_info().await {
+    let (owners, bot_id) = match http.get_current_application_info().await {
         Ok(info) => {
             let mut owners = HashSet::new();
             owners.insert(info.owner.id);
@@ -589,17 +789,35 @@ async fn main() {
         }
         Err(why) => panic!("Could not access application info: {:?}", why),
     };
-    let user: u64 = 696397280433012776;
-    let id = Some(UserId::from(user));
+    let application_id = config.application_id.unwrap_or(bot_id.0);
+    let id = Some(UserId::from(application_id));
+    let buckets = &config.buckets;
     // Create the framework
     let framework = StandardFramework::new()
-        .configure(|c| c.owners(owners).on_mention(id).prefix("daggy "))
+        .configure(|c| {
+            c.owners(owners)
+                .on_mention(id)
+                .prefix("")
+                .dynamic_prefix(dynamic_prefix)
+        })
         .before(before)
-        .bucket("complicated", |b| b.delay(5).time_span(30).limit(2))
+        .bucket("complicated", |b| {
+            b.delay(buckets.complicated.delay)
+                .time_span(buckets.complicated.time_span)
+                .limit(buckets.complicated.limit)
+        })
         .await
-        .bucket("info", |b| b.delay(5).time_span(30).limit(6))
+        .bucket("info", |b| {
+            b.delay(buckets.info.delay)
+                .time_span(buckets.info.time_span)
+                .limit(buckets.info.limit)
+        })
         .await
-        .bucket("dagpi", |b| b.delay(30).time_span(60).limit(2))
+        .bucket("dagpi", |b| {
+            b.delay(buckets.dagpi.delay)
+                .time_span(buckets.dagpi.time_span)
+                .limit(buckets.dagpi.limit)
+        })
         .await
         .after(after)
         .unrecognised_command(unknown_command)
@@ -610,10 +828,22 @@ async fn main() {
         .group(&FUN_GROUP)
         .group(&INFO_GROUP)
         .group(&TEXT_GROUP)
-        .group(&DAGPI_GROUP);
-
-    let mut client = Client::builder(&token)
-        .application_id(696397280433012776_u64)
+        .group(&DAGPI_GROUP)
+        .group(&CONFIG_GROUP)
+        .group(&PREFIX_GROUP)
+        .group(&WARNRULES_GROUP)
+        .group(&AUTOMOD_GROUP)
+        .group(&WORDFILTER_GROUP)
+        .group(&RAID_GROUP)
+        .group(&APPEALS_GROUP)
+        .group(&MODMAIL_GROUP)
+        .group(&VERIFICATION_GROUP)
+        .group(&WELCOME_GROUP)
+        .group(&FAREWELL_GROUP)
+        .group(&REACTIONROLES_GROUP);
+
+    let mut client = Client::builder(token)
+        .application_id(application_id)
         .framework(framework)
         .event_handler(Handler)
         .raw_event_handler(RawHandler)
@@ -621,27 +851,96 @@ async fn main() {
         .await
         .expect("Err creating client");
 
-    let dc = dagpirs::Client::new(&std::env::var("DAGPI_TOKEN").expect("No token")).unwrap();
+    let dc = dagpirs::Client::new(&config.dagpi.token).unwrap();
+    let guild_config = GuildConfigStore::load(config.data_dir.join("guilds.json"))
+        .await
+        .expect("Failed to load guild configuration");
+    let reaction_roles = ReactionRoleStore::load(config.data_dir.join("reaction_roles.json"))
+        .await
+        .expect("Failed to load reaction roles");
+    let role_menus = RoleMenuStore::load(config.data_dir.join("role_menus.json"))
+        .await
+        .expect("Failed to load role menus");
+    let cases = CaseStore::load(config.data_dir.join("cases.json"))
+        .await
+        .expect("Failed to load cases");
+    let warnings = WarningStore::load(config.data_dir.join("warnings.json"))
+        .await
+        .expect("Failed to load warnings");
+    let locks = LockStore::load(config.data_dir.join("locks.json"))
+        .await
+        .expect("Failed to load channel locks");
+    let modmail = ModmailStore::load(
+        config.data_dir.join("modmail.json"),
+        config.data_dir.join("transcripts"),
+    )
+    .await
+    .expect("Failed to load modmail threads");
+    let verified = VerifiedStore::load(config.data_dir.join("verified.json"))
+        .await
+        .expect("Failed to load verifications");
+    let schedule = Schedule::load(config.data_dir.join("schedule.json"))
+        .await
+        .expect("Failed to load scheduled actions");
+    let command_stats = CommandStats::load(config.data_dir.join("command_stats.json"))
+        .await
+        .expect("Failed to load command stats");
+    let event_stats = EventStats::load(config.data_dir.join("event_stats.json"))
+        .await
+        .expect("Failed to load event stats");
 
     {
         let mut data = client.data.write().await;
-        data.insert::<CommandCounter>(HashMap::default());
-        data.insert::<EventCounter>(HashMap::new());
+        data.insert::<CommandCounter>(command_stats);
+        data.insert::<EventCounter>(event_stats);
         data.insert::<UptimerKey>(Uptimer::new());
         data.insert::<utils::client::ClientKey>(utils::client::Client::new());
+        data.insert::<GuildConfigKey>(guild_config);
+        data.insert::<BotConfigKey>(config.clone());
+        data.insert::<ReactionRoleKey>(reaction_roles);
+        data.insert::<RoleMenuKey>(role_menus);
+        data.insert::<SchedulerKey>(schedule);
+        data.insert::<CaseKey>(cases);
+        data.insert::<WarningKey>(warnings);
+        data.insert::<LockKey>(locks);
+        data.insert::<ModmailKey>(modmail);
+        data.insert::<VerifiedKey>(verified);
+        data.insert::<AutomodKey>(RepeatTracker::default());
+        data.insert::<WordFilterKey>(FilterCache::default());
+        data.insert::<RaidKey>(JoinTracker::default());
+        data.insert::<ConfirmKey>(Confirmations::default());
+        data.insert::<ChallengeKey>(Challenges::default());
         data.insert::<ShardManagerContainer>(client.shard_manager.clone());
         data.insert::<dagpirs::Client>(Arc::new(dc))
     }
 
     let shard_manager = client.shard_manager.clone();
+    let shutdown_data = client.data.clone();
 
     tokio::spawn(async move {
         tokio::signal::ctrl_c()
             .await
             .expect("Could not register ctrl+c handler");
+        stats::flush(&shutdown_data).await;
         shard_manager.lock().await.shutdown_all().await;
     });
 
+    tokio::spawn(scheduler::run_forever(
+        client.cache_and_http.http.clone(),
+        client.data.clone(),
+        bot_id,
+    ));
+
+    let flush_data = client.data.clone();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(stats::FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            stats::flush(&flush_data).await;
+        }
+    });
+
     if let Err(why) = client.start().await {
         error!("Client error: {:?}", why);
     }
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use crate::utils::guildconfig;
 use serenity::framework::standard::{
     help_commands::{
         create_customised_help_data, Command, CustomisedHelpData, GroupCommandsPair,
@@ -132,6 +133,7 @@ async fn send_grouped_commands_embed(
     help_description: &str,
     groups: &[GroupCommandsPair],
     colour: Colour,
+    prefixes: &[String],
 ) -> Result<Message, Error> {
     channel_id
         .send_message(&http, |m| {
@@ -143,6 +145,7 @@ async fn send_grouped_commands_embed(
                 }
                 embed.colour(colour);
                 embed.description(help_description);
+                embed.footer(|f| f.text(prefix_footer(prefixes)));
 
                 for group in groups {
                     let mut embed_text = String::default();
@@ -174,12 +177,15 @@ async fn send_single_command_embed(
     channel_id: ChannelId,
     command: &Command<'_>,
     colour: Colour,
+    prefixes: &[String],
 ) -> Result<Message, Error> {
+    let prefix = prefixes.first().map(String::as_str).unwrap_or_default();
     channel_id
         .send_message(&http, |m| {
             m.embed(|embed| {
                 embed.title(&command.name);
                 embed.colour(colour);
+                embed.footer(|f| f.text(prefix_footer(prefixes)));
 
                 if let Some(ref desc) = command.description {
                     embed.description(desc);
@@ -188,33 +194,37 @@ async fn send_single_command_embed(
                 if let Some(ref usage) = command.usage {
                     let full_usage_text = if let Some(first_prefix) = command.group_prefixes.get(0)
                     {
-                        format!("`{} {} {}`", first_prefix, command.name, usage)
+                        format!("`{}{} {} {}`", prefix, first_prefix, command.name, usage)
                     } else {
-                        format!("`{} {}`", command.name, usage)
+                        format!("`{}{} {}`", prefix, command.name, usage)
                     };
 
                     embed.field(&help_options.usage_label, full_usage_text, true);
                 }
 
                 if !command.usage_sample.is_empty() {
-                    let full_example_text = if let Some(first_prefix) =
-                        command.group_prefixes.get(0)
-                    {
-                        let format_example =
-                            |example| format!("`{} {} {}`\n", first_prefix, command.name, example);
-                        command
-                            .usage_sample
-                            .iter()
-                            .map(format_example)
-                            .collect::<String>()
-                    } else {
-                        let format_example = |example| format!("`{} {}`\n", command.name, example);
-                        command
-                            .usage_sample
-                            .iter()
-                            .map(format_example)
-                            .collect::<String>()
-                    };
+                    let full_example_text =
+                        if let Some(first_prefix) = command.group_prefixes.get(0) {
+                            let format_example = |example| {
+                                format!(
+                                    "`{}{} {} {}`\n",
+                                    prefix, first_prefix, command.name, example
+                                )
+                            };
+                            command
+                                .usage_sample
+                                .iter()
+                                .map(format_example)
+                                .collect::<String>()
+                        } else {
+                            let format_example =
+                                |example| format!("`{}{} {}`\n", prefix, command.name, example);
+                            command
+                                .usage_sample
+                                .iter()
+                                .map(format_example)
+                                .collect::<String>()
+                        };
                     embed.field(&help_options.usage_sample_label, full_example_text, true);
                 }
 
@@ -253,6 +263,15 @@ async fn send_single_command_embed(
         .await
 }
 
+fn prefix_footer(prefixes: &[String]) -> String {
+    let quoted = prefixes
+        .iter()
+        .map(|p| format!("\"{}\"", p))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("Prefix: {}", quoted)
+}
+
 fn as_vec(v: &Suggestions) -> &Vec<SuggestedCommandName> {
     &v.0
 }
@@ -360,6 +379,7 @@ pub async fn with_embeds(
 ) -> Option<Message> {
     let formatted_help =
         create_customised_help_data(ctx, msg, &args, &groups, &owners, help_options).await;
+    let prefixes = guildconfig::current_prefixes(ctx, msg.guild_id).await;
 
     let response_result = match formatted_help {
         CustomisedHelpData::SuggestedCommands {
@@ -394,9 +414,10 @@ pub async fn with_embeds(
                 &ctx.http,
                 &help_options,
                 msg.channel_id,
-                &help_description,
+                &help_description.replace("{prefix}", &prefixes[0]),
                 &groups,
                 help_options.embed_success_colour,
+                &prefixes,
             )
             .await
         }
@@ -407,6 +428,7 @@ pub async fn with_embeds(
                 msg.channel_id,
                 &command,
                 help_options.embed_success_colour,
+                &prefixes,
             )
             .await
         }
@@ -0,0 +1,369 @@
+use crate::utils::{
+    cases::{self, Case, CaseAction},
+    guildconfig::{self, GuildConfigKey},
+    moderation, scheduler, wordfilter,
+};
+use serde::{Deserialize, Serialize};
+use serenity::model::guild::{Member, VerificationLevel};
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use tracing::error;
+
+/// Joins this far back are compared for shared traits.
+const TRAIT_WINDOW: i64 = 5 * 60;
+/// Accounts younger than this count as new.
+const NEW_ACCOUNT_AGE: i64 = 7 * 24 * 60 * 60;
+/// Names shorter than this once stripped are too generic to compare.
+const MIN_SKELETON_LEN: usize = 3;
+/// Verification level raid mode raises the server to.
+const RAID_VERIFICATION: VerificationLevel = VerificationLevel::High;
+
+/// Recent joins per guild. Not persisted.
+pub struct RaidKey;
+
+impl TypeMapKey for RaidKey {
+    type Value = JoinTracker;
+}
+
+struct Join {
+    user: UserId,
+    at: i64,
+    /// The name folded to lowercase letters, so `Spammer123` and `spammer_9`
+    /// compare equal.
+    skeleton: String,
+    /// A new account without an avatar.
+    fresh: bool,
+}
+
+#[derive(Default)]
+pub struct JoinTracker {
+    guilds: HashMap<u64, VecDeque<Join>>,
+}
+
+impl JoinTracker {
+    /// Records a join and returns why it looks like a raid, together with the
+    /// members involved, once the guild's threshold is passed.
+    fn record(
+        &mut self,
+        guild: GuildId,
+        join: Join,
+        settings: &RaidSettings,
+    ) -> Option<(String, Vec<UserId>)> {
+        let now = join.at;
+        let window = settings.seconds.max(TRAIT_WINDOW);
+        let joins = self.guilds.entry(guild.0).or_default();
+        while joins.front().map_or(false, |j| now - j.at > window) {
+            joins.pop_front();
+        }
+        joins.push_back(join);
+        let limit = settings.joins as usize;
+
+        let burst = joins
+            .iter()
+            .filter(|j| now - j.at <= settings.seconds)
+            .map(|j| j.user)
+            .collect::<Vec<UserId>>();
+        let found = if burst.len() > limit {
+            Some((
+                format!("{} joins in {} seconds", burst.len(), settings.seconds),
+                burst,
+            ))
+        } else {
+            let mut names: HashMap<&str, Vec<UserId>> = HashMap::new();
+            for j in joins
+                .iter()
+                .filter(|j| j.skeleton.len() >= MIN_SKELETON_LEN)
+            {
+                names.entry(&j.skeleton).or_default().push(j.user);
+            }
+            let fresh = joins
+                .iter()
+                .filter(|j| j.fresh)
+                .map(|j| j.user)
+                .collect::<Vec<UserId>>();
+            match names.into_iter().max_by_key(|(_, users)| users.len()) {
+                Some((name, users)) if users.len() > limit => Some((
+                    format!("{} accounts named like `{}`", users.len(), name),
+                    users,
+                )),
+                _ if fresh.len() > limit => Some((
+                    format!("{} new accounts without an avatar", fresh.len()),
+                    fresh,
+                )),
+                _ => None,
+            }
+        };
+        if found.is_some() {
+            joins.clear();
+        }
+        found
+    }
+}
+
+/// What raid mode does to members who join while it is on.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum RaidAction {
+    Kick,
+    /// Gives the mute role until a moderator looks at them. Falls back to a
+    /// kick when no mute role is set.
+    Quarantine,
+}
+
+impl RaidAction {
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            RaidAction::Kick => "kicked",
+            RaidAction::Quarantine => "quarantined",
+        }
+    }
+}
+
+impl fmt::Display for RaidAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RaidAction::Kick => write!(f, "kick"),
+            RaidAction::Quarantine => write!(f, "quarantine"),
+        }
+    }
+}
+
+impl FromStr for RaidAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "kick" => Ok(RaidAction::Kick),
+            "quarantine" | "mute" => Ok(RaidAction::Quarantine),
+            _ => Err(format!(
+                "`{}` is not a raid action. Use `kick` or `quarantine`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActiveRaid {
+    pub since: i64,
+    pub reason: String,
+    /// Restored when raid mode ends.
+    pub previous_level: Option<VerificationLevel>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RaidSettings {
+    /// Whether joins are watched at all. Raid mode can still be turned on by
+    /// hand when this is off.
+    pub detect: bool,
+    /// Raid mode starts once more than this many suspicious joins are seen.
+    pub joins: u32,
+    pub seconds: i64,
+    pub action: RaidAction,
+    /// Set while raid mode is on, so it survives restarts.
+    pub active: Option<ActiveRaid>,
+}
+
+impl Default for RaidSettings {
+    fn default() -> Self {
+        RaidSettings {
+            detect: false,
+            joins: 10,
+            seconds: 10,
+            action: RaidAction::Kick,
+            active: None,
+        }
+    }
+}
+
+fn skeleton(name: &str) -> String {
+    wordfilter::normalize(name)
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .collect()
+}
+
+/// Watches a join for raids. Returns true when raid mode handled the member,
+/// in which case they should not be welcomed.
+pub async fn on_join(ctx: &Context, guild: GuildId, member: &Member) -> bool {
+    let settings = guildconfig::settings(ctx, guild).await.raid;
+    if settings.active.is_some() {
+        handle(ctx, guild, member.user.id, settings.action).await;
+        return true;
+    }
+    if !settings.detect {
+        return false;
+    }
+    let now = scheduler::now();
+    let join = Join {
+        user: member.user.id,
+        at: now,
+        skeleton: skeleton(&member.user.name),
+        fresh: member.user.avatar.is_none()
+            && now - scheduler::created_at(member.user.id.0) < NEW_ACCOUNT_AGE,
+    };
+    let found = {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<RaidKey>()
+            .expect("Expected Raid in TypeMap.")
+            .record(guild, join, &settings)
+    };
+    let (reason, mut users) = match found {
+        Some(f) => f,
+        None => return false,
+    };
+    if let Err(why) = start(ctx, guild, reason).await {
+        error!("Failed to save raid mode for {}: {:?}", guild, why);
+    }
+    // Raid mode is on now, so this join is handled like any later one.
+    if !users.contains(&member.user.id) {
+        users.push(member.user.id);
+    }
+    for user in users {
+        handle(ctx, guild, user, settings.action).await;
+    }
+    true
+}
+
+/// Turns raid mode on, raises the verification level and alerts the staff.
+/// Returns false when raid mode was already on.
+pub async fn start(ctx: &Context, guild: GuildId, reason: String) -> io::Result<bool> {
+    if guildconfig::settings(ctx, guild)
+        .await
+        .raid
+        .active
+        .is_some()
+    {
+        return Ok(false);
+    }
+    let current = guild
+        .to_guild_cached(&ctx.cache)
+        .await
+        .map(|g| g.verification_level);
+    let mut previous_level = None;
+    if current.map_or(true, |level| level.num() < RAID_VERIFICATION.num()) {
+        let mut editing = guild;
+        match editing
+            .edit(&ctx.http, |g| g.verification_level(RAID_VERIFICATION))
+            .await
+        {
+            Ok(_) => previous_level = current,
+            Err(why) => error!("Failed to raise verification for {}: {:?}", guild, why),
+        }
+    }
+    let action = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let raid = &mut store.get_mut(guild).raid;
+        raid.active = Some(ActiveRaid {
+            since: scheduler::now(),
+            reason: reason.clone(),
+            previous_level,
+        });
+        let action = raid.action;
+        store.save().await?;
+        action
+    };
+    let prefix = guildconfig::current_prefixes(ctx, Some(guild))
+        .await
+        .remove(0);
+    alert(
+        ctx,
+        guild,
+        format!(
+            "**Raid mode is on**: {}\nNew members will be {}{}. End it with `{}raid off`",
+            reason,
+            action.past_tense(),
+            if previous_level.is_some() {
+                " and the verification level was raised"
+            } else {
+                ""
+            },
+            prefix
+        ),
+    )
+    .await;
+    Ok(true)
+}
+
+/// Turns raid mode off and restores the verification level. Returns the raid
+/// that ended, if one was on.
+pub async fn end(ctx: &Context, guild: GuildId) -> io::Result<Option<ActiveRaid>> {
+    let ended = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.");
+        let ended = store.get_mut(guild).raid.active.take();
+        if ended.is_some() {
+            store.save().await?;
+        }
+        ended
+    };
+    if let Some(level) = ended.as_ref().and_then(|r| r.previous_level) {
+        let mut editing = guild;
+        if let Err(why) = editing
+            .edit(&ctx.http, |g| g.verification_level(level))
+            .await
+        {
+            error!("Failed to restore verification for {}: {:?}", guild, why);
+        }
+    }
+    Ok(ended)
+}
+
+async fn handle(ctx: &Context, guild: GuildId, user: UserId, action: RaidAction) {
+    let bot = ctx.cache.current_user_id().await;
+    let mute_role = guildconfig::settings(ctx, guild).await.mute_role;
+    let result = match (action, mute_role) {
+        (RaidAction::Quarantine, Some(role)) => {
+            match ctx.http.add_member_role(guild.0, user.0, role.0).await {
+                Ok(_) => {
+                    let case = Case::new(
+                        CaseAction::Mute,
+                        user,
+                        bot,
+                        Some("Raid mode quarantine".to_string()),
+                    );
+                    cases::open(&ctx.http, &ctx.data, guild, case).await;
+                    Ok(())
+                }
+                Err(why) => Err(why.to_string()),
+            }
+        }
+        _ => moderation::kick(ctx, guild, user, bot, "Raid mode".to_string())
+            .await
+            .map(|_| ())
+            .map_err(|why| why.to_string()),
+    };
+    if let Err(why) = result {
+        error!("Raid mode couldn't {} {}: {}", action, user, why);
+    }
+}
+
+/// Posts to the staff channel, or the mod log when there is none.
+async fn alert(ctx: &Context, guild: GuildId, text: String) {
+    let settings = guildconfig::settings(ctx, guild).await;
+    if let Some(channel) = settings.staff_channel.or(settings.modlog_channel) {
+        let sent = channel
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title("Raid Alert");
+                    e.description(text);
+                    e.color(Colour::RED);
+                    e
+                })
+            })
+            .await;
+        if let Err(why) = sent {
+            error!("Failed to post raid alert: {:?}", why);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use crate::utils::{
+    cases::{Case, CaseKey},
+    guildconfig::{self, GuildSettings},
+    moderation, scheduler,
+};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::model::interactions::{
+    message_component::{ButtonStyle, MessageComponentInteraction},
+    InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+};
+use serenity::model::user::User;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::fmt;
+use tracing::error;
+
+/// Custom ids look like `appeal:<guild>:<case>:accept` or `...:deny`.
+pub const ID_PREFIX: &str = "appeal:";
+/// How many times one ban can be appealed.
+pub const MAX_APPEALS: usize = 3;
+pub const MAX_LENGTH: usize = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AppealStatus {
+    Pending,
+    Accepted,
+    Denied,
+}
+
+/// An appeal against a ban, stored on the ban's case.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Appeal {
+    pub text: String,
+    /// Unix timestamps in seconds.
+    pub submitted_at: i64,
+    pub status: AppealStatus,
+    pub reviewer: Option<UserId>,
+    pub reviewed_at: Option<i64>,
+}
+
+impl fmt::Display for Appeal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<t:{}:d> ", self.submitted_at)?;
+        match (self.status, self.reviewer) {
+            (AppealStatus::Pending, _) => write!(f, "Pending"),
+            (AppealStatus::Accepted, Some(r)) => write!(f, "Accepted by <@{}>", r.0),
+            (AppealStatus::Denied, Some(r)) => write!(f, "Denied by <@{}>", r.0),
+            (AppealStatus::Accepted, None) => write!(f, "Accepted"),
+            (AppealStatus::Denied, None) => write!(f, "Denied"),
+        }
+    }
+}
+
+/// Where appeals are reviewed: the staff channel, or the mod log if there is
+/// none. Guilds with neither don't take appeals.
+pub fn review_channel(settings: &GuildSettings) -> Option<ChannelId> {
+    settings.staff_channel.or(settings.modlog_channel)
+}
+
+/// Why another appeal can't be made against `case`, if it can't.
+pub fn refusal(case: &Case) -> Option<String> {
+    if case
+        .appeals
+        .last()
+        .map_or(false, |a| a.status == AppealStatus::Pending)
+    {
+        Some("Your last appeal is still being reviewed".to_string())
+    } else if case.appeals.len() >= MAX_APPEALS {
+        Some(format!(
+            "This ban has already been appealed {} times",
+            MAX_APPEALS
+        ))
+    } else {
+        None
+    }
+}
+
+/// The servers `user` is banned from and can appeal to, with the ban's case.
+pub async fn appealable(ctx: &Context, user: UserId) -> Vec<(GuildId, Case)> {
+    let bans = {
+        let data = ctx.data.read().await;
+        let store = data.get::<CaseKey>().expect("Expected Cases in TypeMap.");
+        store
+            .bans_of(user)
+            .into_iter()
+            .filter_map(|(guild, number)| store.get(guild, number).map(|c| (guild, c.clone())))
+            .collect::<Vec<_>>()
+    };
+    let mut open = Vec::new();
+    for (guild, case) in bans {
+        if review_channel(&guildconfig::settings(ctx, guild).await).is_none() {
+            continue;
+        }
+        // The ban may have been lifted without a case, or the bot may have
+        // left the server.
+        if scheduler::is_banned(&ctx.http, guild, user)
+            .await
+            .unwrap_or(false)
+        {
+            open.push((guild, case));
+        }
+    }
+    open
+}
+
+/// Posts an appeal for staff to review and records it on the ban case.
+pub async fn submit(
+    ctx: &Context,
+    guild: GuildId,
+    case: &Case,
+    user: &User,
+    text: String,
+) -> serenity::Result<()> {
+    let channel = match review_channel(&guildconfig::settings(ctx, guild).await) {
+        Some(c) => c,
+        None => return Ok(()),
+    };
+    let id = |choice: &str| format!("{}{}:{}:{}", ID_PREFIX, guild.0, case.number, choice);
+    channel
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("Ban Appeal | Case #{}", case.number));
+                e.author(|a| a.name(user.tag()).icon_url(user.face()));
+                e.field("User", format!("<@{}> ({})", user.id.0, user.id.0), true);
+                e.field(
+                    "Appeal",
+                    format!("{} of {}", case.appeals.len() + 1, MAX_APPEALS),
+                    true,
+                );
+                e.field(
+                    "Ban Reason",
+                    case.reason.as_deref().unwrap_or("No reason given"),
+                    false,
+                );
+                e.field("Message", &text, false);
+                e.color(Colour::BLURPLE);
+                e
+            })
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.style(ButtonStyle::Success)
+                            .label("Accept")
+                            .custom_id(id("accept"))
+                    })
+                    .create_button(|b| {
+                        b.style(ButtonStyle::Danger)
+                            .label("Deny")
+                            .custom_id(id("deny"))
+                    })
+                })
+            })
+        })
+        .await?;
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<CaseKey>()
+        .expect("Expected Cases in TypeMap.");
+    if let Some(stored) = store.get_mut(guild, case.number) {
+        stored.appeals.push(Appeal {
+            text,
+            submitted_at: scheduler::now(),
+            status: AppealStatus::Pending,
+            reviewer: None,
+            reviewed_at: None,
+        });
+    }
+    if let Err(why) = store.save().await {
+        error!("Failed to save cases: {}", why);
+    }
+    Ok(())
+}
+
+async fn reply_privately(ctx: &Context, component: &MessageComponentInteraction, text: String) {
+    let _ = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
+}
+
+/// Accepts or denies an appeal from its buttons. Reviewers need the Ban
+/// Members permission; accepting unbans the user like `unban` does.
+pub async fn handle_component(ctx: &Context, component: &MessageComponentInteraction) {
+    let mut parts = component.data.custom_id[ID_PREFIX.len()..].split(':');
+    let guild = parts.next().and_then(|p| p.parse::<u64>().ok());
+    let number = parts.next().and_then(|p| p.parse::<u64>().ok());
+    let accept = parts.next() == Some("accept");
+    let (guild, number) = match (guild, number) {
+        (Some(g), Some(n)) if component.guild_id == Some(GuildId(g)) => (GuildId(g), n),
+        _ => return,
+    };
+    let reviewer = component.user.id;
+
+    let case = {
+        let data = ctx.data.read().await;
+        data.get::<CaseKey>()
+            .expect("Expected Cases in TypeMap.")
+            .get(guild, number)
+            .cloned()
+    };
+    let case = match case {
+        Some(c)
+            if c.appeals
+                .last()
+                .map_or(false, |a| a.status == AppealStatus::Pending) =>
+        {
+            c
+        }
+        _ => {
+            reply_privately(
+                ctx,
+                component,
+                "This appeal has already been handled".to_string(),
+            )
+            .await;
+            return;
+        }
+    };
+    let checked = if accept {
+        let reason = format!("Appeal on case #{} accepted", number);
+        moderation::unban(ctx, guild, case.target, reviewer, Some(reason))
+            .await
+            .map(|_| ())
+    } else {
+        moderation::check_target(ctx, guild, reviewer, case.target, case.action).await
+    };
+    if let Err(why) = checked {
+        reply_privately(ctx, component, why.to_string()).await;
+        return;
+    }
+    let status = if accept {
+        AppealStatus::Accepted
+    } else {
+        AppealStatus::Denied
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<CaseKey>()
+            .expect("Expected Cases in TypeMap.");
+        if let Some(appeal) = store
+            .get_mut(guild, number)
+            .and_then(|c| c.appeals.last_mut())
+        {
+            appeal.status = status;
+            appeal.reviewer = Some(reviewer);
+            appeal.reviewed_at = Some(scheduler::now());
+        }
+        if let Err(why) = store.save().await {
+            error!("Failed to save cases: {}", why);
+        }
+    }
+
+    let name = moderation::guild_name(ctx, guild).await;
+    let outcome = if accept {
+        format!(
+            "Your ban appeal for **{}** was accepted. You have been unbanned",
+            name
+        )
+    } else {
+        let left = MAX_APPEALS.saturating_sub(case.appeals.len());
+        format!(
+            "Your ban appeal for **{}** was denied. You can appeal {} more time(s)",
+            name, left
+        )
+    };
+    let told = moderation::notify(ctx, case.target, outcome).await;
+    let text = format!(
+        "{} by <@{}>{}",
+        if accept { "Accepted" } else { "Denied" },
+        reviewer.0,
+        if told { "" } else { ". Couldn't DM them" }
+    );
+    let _ = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(text).components(|c| c))
+        })
+        .await;
+}
@@ -0,0 +1,370 @@
+use crate::utils::{
+    guildconfig,
+    moderation::{self, ActionError},
+    modlog, scheduler,
+    timeparser::HumanDuration,
+    warnings,
+};
+use serde::{Deserialize, Serialize};
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use tracing::error;
+
+/// Identical messages further apart than this don't count as repeats.
+const REPEAT_WINDOW: i64 = 30;
+/// Members tracked before stale entries are dropped.
+const TRACKER_LIMIT: usize = 10_000;
+/// Short messages are ignored by the caps filter.
+const CAPS_MIN_LETTERS: usize = 10;
+const INVITE_LINKS: [&str; 4] = [
+    "discord.gg/",
+    "discord.com/invite/",
+    "discordapp.com/invite/",
+    "discord.me/",
+];
+
+/// Recent messages per member, for the repeat filter. Not persisted.
+pub struct AutomodKey;
+
+impl TypeMapKey for AutomodKey {
+    type Value = RepeatTracker;
+}
+
+#[derive(Default)]
+pub struct RepeatTracker {
+    /// (guild, user) → (last content, times in a row, last sent at).
+    last: HashMap<(u64, u64), (String, u32, i64)>,
+}
+
+impl RepeatTracker {
+    /// Records a message and returns how many times in a row it was sent.
+    fn record(&mut self, guild: GuildId, user: UserId, content: &str, now: i64) -> u32 {
+        if self.last.len() > TRACKER_LIMIT {
+            self.last.retain(|_, (_, _, at)| now - *at <= REPEAT_WINDOW);
+        }
+        let content = content.trim().to_lowercase();
+        let entry = self
+            .last
+            .entry((guild.0, user.0))
+            .or_insert_with(|| (String::new(), 0, 0));
+        if entry.0 == content && now - entry.2 <= REPEAT_WINDOW {
+            entry.1 += 1;
+            entry.2 = now;
+        } else {
+            *entry = (content, 1, now);
+        }
+        entry.1
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Repeats,
+    Mentions,
+    Invites,
+    Caps,
+    Emoji,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 5] = [
+        Filter::Repeats,
+        Filter::Mentions,
+        Filter::Invites,
+        Filter::Caps,
+        Filter::Emoji,
+    ];
+
+    /// What the threshold means for this filter.
+    pub fn threshold_help(&self) -> &'static str {
+        match self {
+            Filter::Repeats => "identical messages in a row",
+            Filter::Mentions => "mentions in one message",
+            Filter::Invites => "invite links in one message",
+            Filter::Caps => "percent capital letters",
+            Filter::Emoji => "emoji in one message",
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Filter::Repeats => "repeats",
+            Filter::Mentions => "mentions",
+            Filter::Invites => "invites",
+            Filter::Caps => "caps",
+            Filter::Emoji => "emoji",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "repeats" | "repeat" | "spam" => Ok(Filter::Repeats),
+            "mentions" | "mention" => Ok(Filter::Mentions),
+            "invites" | "invite" => Ok(Filter::Invites),
+            "caps" => Ok(Filter::Caps),
+            "emoji" | "emojis" => Ok(Filter::Emoji),
+            _ => Err(format!(
+                "`{}` is not a filter. Choose one of `repeats`, `mentions`, `invites`, `caps` or `emoji`",
+                s
+            )),
+        }
+    }
+}
+
+/// What happens to a message that trips a filter. The message is deleted in
+/// every case.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AutomodAction {
+    Delete,
+    Warn,
+    /// Mute for this many seconds.
+    Mute(i64),
+    Kick,
+}
+
+impl fmt::Display for AutomodAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutomodAction::Delete => write!(f, "delete"),
+            AutomodAction::Warn => write!(f, "warn"),
+            AutomodAction::Mute(secs) => write!(
+                f,
+                "mute for {}",
+                HumanDuration(time::Duration::seconds(*secs))
+            ),
+            AutomodAction::Kick => write!(f, "kick"),
+        }
+    }
+}
+
+impl FromStr for AutomodAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(2, char::is_whitespace);
+        let kind = parts.next().unwrap_or_default().to_lowercase();
+        match (kind.as_str(), parts.next()) {
+            ("delete", None) => Ok(AutomodAction::Delete),
+            ("warn", None) => Ok(AutomodAction::Warn),
+            ("mute", Some(d)) => d
+                .parse::<HumanDuration>()
+                .map(|d| AutomodAction::Mute(d.whole_seconds()))
+                .map_err(|e| e.to_string()),
+            ("mute", None) => Err("A mute needs a duration, like `mute 10m`".to_string()),
+            ("kick", None) => Ok(AutomodAction::Kick),
+            _ => Err(format!(
+                "`{}` is not an action. Use `delete`, `warn`, `mute <duration>` or `kick`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Rule {
+    pub enabled: bool,
+    pub threshold: u32,
+    pub action: AutomodAction,
+}
+
+impl Rule {
+    const fn off(threshold: u32, action: AutomodAction) -> Rule {
+        Rule {
+            enabled: false,
+            threshold,
+            action,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AutomodSettings {
+    pub enabled: bool,
+    pub repeats: Rule,
+    pub mentions: Rule,
+    pub invites: Rule,
+    pub caps: Rule,
+    pub emoji: Rule,
+    pub exempt_channels: Vec<ChannelId>,
+    pub exempt_roles: Vec<RoleId>,
+}
+
+impl Default for AutomodSettings {
+    fn default() -> Self {
+        AutomodSettings {
+            enabled: false,
+            repeats: Rule::off(4, AutomodAction::Delete),
+            mentions: Rule::off(6, AutomodAction::Mute(10 * 60)),
+            invites: Rule::off(1, AutomodAction::Delete),
+            caps: Rule::off(70, AutomodAction::Delete),
+            emoji: Rule::off(10, AutomodAction::Delete),
+            exempt_channels: Vec::new(),
+            exempt_roles: Vec::new(),
+        }
+    }
+}
+
+impl AutomodSettings {
+    pub fn rule(&self, filter: Filter) -> &Rule {
+        match filter {
+            Filter::Repeats => &self.repeats,
+            Filter::Mentions => &self.mentions,
+            Filter::Invites => &self.invites,
+            Filter::Caps => &self.caps,
+            Filter::Emoji => &self.emoji,
+        }
+    }
+
+    pub fn rule_mut(&mut self, filter: Filter) -> &mut Rule {
+        match filter {
+            Filter::Repeats => &mut self.repeats,
+            Filter::Mentions => &mut self.mentions,
+            Filter::Invites => &mut self.invites,
+            Filter::Caps => &mut self.caps,
+            Filter::Emoji => &mut self.emoji,
+        }
+    }
+}
+
+fn caps_percent(content: &str) -> u32 {
+    let letters = content.chars().filter(|c| c.is_alphabetic()).count();
+    if letters < CAPS_MIN_LETTERS {
+        return 0;
+    }
+    let upper = content.chars().filter(|c| c.is_uppercase()).count();
+    (upper * 100 / letters) as u32
+}
+
+/// Custom emoji (`<:name:id>` and `<a:name:id>`) plus the common unicode
+/// emoji blocks.
+fn emoji_count(content: &str) -> u32 {
+    let custom = content.matches("<:").count() + content.matches("<a:").count();
+    let unicode = content
+        .chars()
+        .filter(|c| matches!(*c as u32, 0x1F300..=0x1FAFF | 0x2600..=0x27BF))
+        .count();
+    (custom + unicode) as u32
+}
+
+fn invite_count(content: &str) -> u32 {
+    let content = content.to_lowercase();
+    INVITE_LINKS
+        .iter()
+        .map(|link| content.matches(link).count() as u32)
+        .sum()
+}
+
+/// How far a message goes on each filter's scale.
+async fn measure(ctx: &Context, msg: &Message, guild: GuildId, filter: Filter) -> u32 {
+    match filter {
+        Filter::Repeats => {
+            let mut data = ctx.data.write().await;
+            data.get_mut::<AutomodKey>()
+                .expect("Expected Automod in TypeMap.")
+                .record(guild, msg.author.id, &msg.content, scheduler::now())
+        }
+        Filter::Mentions => {
+            (msg.mentions.len() + msg.mention_roles.len() + msg.mention_everyone as usize) as u32
+        }
+        Filter::Invites => invite_count(&msg.content),
+        Filter::Caps => caps_percent(&msg.content),
+        Filter::Emoji => emoji_count(&msg.content),
+    }
+}
+
+/// Runs the guild's automod rules against a message. Only the first rule that
+/// trips is acted on.
+pub async fn check(ctx: &Context, msg: &Message) {
+    let guild = match msg.guild_id {
+        Some(g) if !msg.author.bot => g,
+        _ => return,
+    };
+    let settings = guildconfig::settings(ctx, guild).await.automod;
+    if !settings.enabled || settings.exempt_channels.contains(&msg.channel_id) {
+        return;
+    }
+    let exempt_role = msg.member.as_ref().map_or(false, |m| {
+        m.roles.iter().any(|r| settings.exempt_roles.contains(r))
+    });
+    if exempt_role {
+        return;
+    }
+
+    for filter in Filter::ALL.iter().copied() {
+        let rule = *settings.rule(filter);
+        if !rule.enabled {
+            continue;
+        }
+        let measured = measure(ctx, msg, guild, filter).await;
+        if measured >= rule.threshold {
+            let reason = format!(
+                "Automod: {} ({} {})",
+                filter,
+                measured,
+                filter.threshold_help()
+            );
+            if let Err(why) = punish(ctx, msg, guild, rule.action, reason).await {
+                error!(
+                    "Automod couldn't {} {}: {}",
+                    rule.action, msg.author.id, why
+                );
+            }
+            return;
+        }
+    }
+}
+
+async fn punish(
+    ctx: &Context,
+    msg: &Message,
+    guild: GuildId,
+    action: AutomodAction,
+    reason: String,
+) -> Result<(), ActionError> {
+    let _ = msg.delete(ctx).await;
+    modlog::post_deletion(
+        &ctx.http,
+        &ctx.data,
+        guild,
+        msg.author.id,
+        msg.channel_id,
+        &reason,
+        &msg.content,
+    )
+    .await;
+    let bot = ctx.cache.current_user_id().await;
+    match action {
+        AutomodAction::Delete => {}
+        AutomodAction::Warn => {
+            let (_, _, escalation) =
+                warnings::warn(ctx, guild, msg.author.id, bot, reason, msg.channel_id).await?;
+            escalation?;
+        }
+        AutomodAction::Mute(secs) => {
+            let length = HumanDuration(time::Duration::seconds(secs));
+            match moderation::mute(ctx, guild, msg.author.id, bot, Some(reason), length).await {
+                // Spam often keeps arriving for a moment after the mute.
+                Err(ActionError::AlreadyMuted) => {}
+                other => {
+                    other?;
+                }
+            }
+        }
+        AutomodAction::Kick => {
+            moderation::kick(ctx, guild, msg.author.id, bot, reason).await?;
+        }
+    }
+    Ok(())
+}
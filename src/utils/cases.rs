@@ -0,0 +1,234 @@
+use crate::utils::{appeals::Appeal, modlog, scheduler, storage, timeparser::HumanDuration};
+use serde::{Deserialize, Serialize};
+use serenity::builder::CreateEmbed;
+use serenity::http::Http;
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use tracing::error;
+
+/// Discord's limit for an embed field, which is where a case's reason goes.
+pub const MAX_REASON_LEN: usize = 1024;
+
+pub struct CaseKey;
+
+impl TypeMapKey for CaseKey {
+    type Value = CaseStore;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CaseAction {
+    Warn,
+    Kick,
+    Ban,
+    Tempban,
+    /// Ban and immediate unban, to clear a member's messages.
+    Softban,
+    Unban,
+    Mute,
+    Unmute,
+}
+
+impl fmt::Display for CaseAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CaseAction::Warn => "Warn",
+            CaseAction::Kick => "Kick",
+            CaseAction::Ban => "Ban",
+            CaseAction::Tempban => "Tempban",
+            CaseAction::Softban => "Softban",
+            CaseAction::Unban => "Unban",
+            CaseAction::Mute => "Mute",
+            CaseAction::Unmute => "Unmute",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl CaseAction {
+    fn colour(&self) -> Colour {
+        match self {
+            CaseAction::Warn => Colour::GOLD,
+            CaseAction::Kick | CaseAction::Softban => Colour::ORANGE,
+            CaseAction::Ban | CaseAction::Tempban => Colour::RED,
+            CaseAction::Mute => Colour::DARK_ORANGE,
+            CaseAction::Unban | CaseAction::Unmute => Colour::DARK_GREEN,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Case {
+    /// Numbered from 1 within each guild. Set by [`CaseStore::open`].
+    pub number: u64,
+    pub action: CaseAction,
+    pub target: UserId,
+    pub moderator: UserId,
+    pub reason: Option<String>,
+    /// Unix timestamps in seconds.
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    /// Ban appeals made against this case, oldest first.
+    #[serde(default)]
+    pub appeals: Vec<Appeal>,
+}
+
+impl Case {
+    pub fn new(
+        action: CaseAction,
+        target: UserId,
+        moderator: UserId,
+        reason: Option<String>,
+    ) -> Case {
+        Case {
+            number: 0,
+            action,
+            target,
+            moderator,
+            reason: reason.filter(|r| !r.trim().is_empty()),
+            created_at: scheduler::now(),
+            expires_at: None,
+            appeals: Vec::new(),
+        }
+    }
+
+    pub fn expires(mut self, at: i64) -> Case {
+        self.expires_at = Some(at);
+        self
+    }
+
+    pub fn embed(&self, e: &mut CreateEmbed) {
+        e.title(format!("Case #{} | {}", self.number, self.action));
+        e.field(
+            "User",
+            format!("<@{}> ({})", self.target.0, self.target.0),
+            true,
+        );
+        e.field("Moderator", format!("<@{}>", self.moderator.0), true);
+        e.field(
+            "Reason",
+            shorten(
+                self.reason.as_deref().unwrap_or("No reason given"),
+                MAX_REASON_LEN,
+            ),
+            false,
+        );
+        if let Some(at) = self.expires_at {
+            let length = HumanDuration(time::Duration::seconds(at - self.created_at));
+            e.field("Duration", length, true);
+            e.field("Expires", format!("<t:{}:f> (<t:{}:R>)", at, at), false);
+        }
+        e.field("Date", format!("<t:{}:f>", self.created_at), false);
+        if !self.appeals.is_empty() {
+            let appeals = self
+                .appeals
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>()
+                .join("\n");
+            e.field("Appeals", appeals, false);
+        }
+        e.color(self.action.colour());
+    }
+}
+
+/// Cuts `text` to at most `max` characters, ending in an ellipsis if
+/// anything was cut.
+pub fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut short = text.chars().take(max - 1).collect::<String>();
+    short.push('…');
+    short
+}
+
+pub struct CaseStore {
+    path: PathBuf,
+    guilds: HashMap<u64, Vec<Case>>,
+}
+
+impl CaseStore {
+    pub async fn load(path: PathBuf) -> io::Result<CaseStore> {
+        let guilds = storage::load(&path).await?;
+        Ok(CaseStore { path, guilds })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.guilds).await
+    }
+
+    /// Numbers the case and stores it. Returns the stored case.
+    pub fn open(&mut self, guild: GuildId, mut case: Case) -> &Case {
+        let cases = self.guilds.entry(guild.0).or_default();
+        case.number = cases.last().map_or(1, |c| c.number + 1);
+        cases.push(case);
+        cases.last().unwrap()
+    }
+
+    pub fn get(&self, guild: GuildId, number: u64) -> Option<&Case> {
+        self.guilds
+            .get(&guild.0)?
+            .iter()
+            .find(|c| c.number == number)
+    }
+
+    pub fn get_mut(&mut self, guild: GuildId, number: u64) -> Option<&mut Case> {
+        self.guilds
+            .get_mut(&guild.0)?
+            .iter_mut()
+            .find(|c| c.number == number)
+    }
+
+    /// The guilds where the latest ban or unban case against `user` is a
+    /// ban, with that case's number.
+    pub fn bans_of(&self, user: UserId) -> Vec<(GuildId, u64)> {
+        self.guilds
+            .iter()
+            .filter_map(|(guild, cases)| {
+                cases
+                    .iter()
+                    .rev()
+                    .filter(|c| c.target == user)
+                    .find(|c| {
+                        matches!(
+                            c.action,
+                            CaseAction::Ban | CaseAction::Tempban | CaseAction::Unban
+                        )
+                    })
+                    .filter(|c| c.action != CaseAction::Unban)
+                    .map(|c| (GuildId(*guild), c.number))
+            })
+            .collect()
+    }
+
+    pub fn for_user(&self, guild: GuildId, user: UserId) -> Vec<&Case> {
+        match self.guilds.get(&guild.0) {
+            Some(cases) => cases.iter().filter(|c| c.target == user).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Opens a case, saves the store and posts the case to the mod log. Failures
+/// are logged rather than returned since the action itself has already
+/// happened by the time it is recorded.
+pub async fn open(http: &Http, data: &RwLock<TypeMap>, guild: GuildId, case: Case) -> Case {
+    let case = {
+        let mut data = data.write().await;
+        let store = data
+            .get_mut::<CaseKey>()
+            .expect("Expected Cases in TypeMap.");
+        let case = store.open(guild, case).clone();
+        if let Err(why) = store.save().await {
+            error!("Failed to save cases: {}", why);
+        }
+        case
+    };
+    modlog::post_case(http, data, guild, &case).await;
+    case
+}
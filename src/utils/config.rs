@@ -0,0 +1,206 @@
+use serde::Deserialize;
+use serenity::prelude::*;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub struct BotConfigKey;
+
+impl TypeMapKey for BotConfigKey {
+    type Value = Arc<BotConfig>;
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    pub prefix: String,
+    pub application_id: Option<u64>,
+    pub data_dir: PathBuf,
+    pub discord: DiscordConfig,
+    pub dagpi: DagpiConfig,
+    pub sentry: SentryConfig,
+    pub buckets: Buckets,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    pub token: String,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DagpiConfig {
+    pub token: String,
+    pub admin_token: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct SentryConfig {
+    pub dsn: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Buckets {
+    pub complicated: Bucket,
+    pub info: Bucket,
+    pub dagpi: Bucket,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Bucket {
+    pub delay: u64,
+    pub time_span: u64,
+    pub limit: u32,
+}
+
+impl Default for BotConfig {
+    fn default() -> BotConfig {
+        BotConfig {
+            prefix: "daggy ".to_string(),
+            application_id: None,
+            data_dir: PathBuf::from("data"),
+            discord: DiscordConfig::default(),
+            dagpi: DagpiConfig::default(),
+            sentry: SentryConfig::default(),
+            buckets: Buckets::default(),
+        }
+    }
+}
+
+impl Default for Buckets {
+    fn default() -> Buckets {
+        Buckets {
+            complicated: Bucket {
+                delay: 5,
+                time_span: 30,
+                limit: 2,
+            },
+            info: Bucket {
+                delay: 5,
+                time_span: 30,
+                limit: 6,
+            },
+            dagpi: Bucket {
+                delay: 30,
+                time_span: 60,
+                limit: 2,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Missing(&'static str, &'static str),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            ConfigError::Missing(key, var) => write!(
+                f,
+                "`{}` is not set. Add it to the config file or set the `{}` environment variable",
+                key, var
+            ),
+            ConfigError::Invalid(key, reason) => write!(f, "`{}` is invalid: {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn env_override(var: &str) -> Option<String> {
+    env::var(var).ok().filter(|v| !v.is_empty())
+}
+
+impl BotConfig {
+    /// Loads the config file named by `DAGGY_CONFIG` (default `config.toml`),
+    /// applies environment overrides and validates the result. A missing file
+    /// is fine as long as the environment supplies the required values.
+    pub fn load() -> Result<BotConfig, ConfigError> {
+        let path = PathBuf::from(
+            env_override("DAGGY_CONFIG").unwrap_or_else(|| "config.toml".to_string()),
+        );
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                toml::from_str::<BotConfig>(&text).map_err(|e| ConfigError::Parse(path, e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BotConfig::default(),
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+        config.apply_env();
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) {
+        if let Some(v) = env_override("DISCORD_TOKEN") {
+            self.discord.token = v;
+        }
+        if let Some(v) = env_override("DAGPI_TOKEN") {
+            self.dagpi.token = v;
+        }
+        if let Some(v) = env_override("DAGPI_ADMIN") {
+            self.dagpi.admin_token = Some(v);
+        }
+        if let Some(v) = env_override("SENTRY") {
+            self.sentry.dsn = Some(v);
+        }
+        if let Some(v) = env_override("DAGGY_PREFIX") {
+            self.prefix = v;
+        }
+        if let Some(v) = env_override("DAGGY_DATA_DIR") {
+            self.data_dir = PathBuf::from(v);
+        }
+        // Empty strings in the file mean "not configured".
+        self.dagpi.admin_token = self.dagpi.admin_token.take().filter(|t| !t.is_empty());
+        self.sentry.dsn = self.sentry.dsn.take().filter(|d| !d.is_empty());
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.discord.token.is_empty() {
+            return Err(ConfigError::Missing("discord.token", "DISCORD_TOKEN"));
+        }
+        if self.dagpi.token.is_empty() {
+            return Err(ConfigError::Missing("dagpi.token", "DAGPI_TOKEN"));
+        }
+        if self.prefix.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "prefix",
+                "it must contain at least one visible character".to_string(),
+            ));
+        }
+        if let Some(dsn) = &self.sentry.dsn {
+            if dsn.parse::<sentry::types::Dsn>().is_err() {
+                return Err(ConfigError::Invalid(
+                    "sentry.dsn",
+                    format!("`{}` is not a Sentry DSN", dsn),
+                ));
+            }
+        }
+        let buckets = [
+            ("buckets.complicated", self.buckets.complicated),
+            ("buckets.info", self.buckets.info),
+            ("buckets.dagpi", self.buckets.dagpi),
+        ];
+        for &(name, bucket) in buckets.iter() {
+            if bucket.limit == 0 || bucket.time_span == 0 {
+                return Err(ConfigError::Invalid(
+                    name,
+                    "`limit` and `time_span` must be greater than 0".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use serenity::model::interactions::{
+    message_component::{ButtonStyle, MessageComponentInteraction},
+    InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Custom ids look like `confirm:<command message>:yes` or `...:no`.
+pub const ID_PREFIX: &str = "confirm:";
+/// Unanswered prompts are cancelled after this long.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Prompts waiting for their buttons to be clicked. Not persisted.
+pub struct ConfirmKey;
+
+impl TypeMapKey for ConfirmKey {
+    type Value = Confirmations;
+}
+
+#[derive(Default)]
+pub struct Confirmations {
+    pending: HashMap<u64, (UserId, oneshot::Sender<bool>)>,
+}
+
+/// Asks the author of `msg` to confirm `question` with a button. Returns
+/// false if they cancel or don't answer in time.
+pub async fn ask(ctx: &Context, msg: &Message, question: String) -> serenity::Result<bool> {
+    let (sender, receiver) = oneshot::channel();
+    {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<ConfirmKey>()
+            .expect("Expected Confirmations in TypeMap.")
+            .pending
+            .insert(msg.id.0, (msg.author.id, sender));
+    }
+    let mut prompt = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(question).components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.style(ButtonStyle::Danger)
+                            .label("Confirm")
+                            .custom_id(format!("{}{}:yes", ID_PREFIX, msg.id.0))
+                    })
+                    .create_button(|b| {
+                        b.style(ButtonStyle::Secondary)
+                            .label("Cancel")
+                            .custom_id(format!("{}{}:no", ID_PREFIX, msg.id.0))
+                    })
+                })
+            })
+        })
+        .await?;
+    match tokio::time::timeout(TIMEOUT, receiver).await {
+        Ok(Ok(answer)) => Ok(answer),
+        _ => {
+            {
+                let mut data = ctx.data.write().await;
+                data.get_mut::<ConfirmKey>()
+                    .expect("Expected Confirmations in TypeMap.")
+                    .pending
+                    .remove(&msg.id.0);
+            }
+            let _ = prompt
+                .edit(&ctx, |m| m.content("Timed out").components(|c| c))
+                .await;
+            Ok(false)
+        }
+    }
+}
+
+/// Passes a click on a confirmation button back to the waiting command. Only
+/// the person who ran the command can answer.
+pub async fn handle_component(ctx: &Context, component: &MessageComponentInteraction) {
+    let mut parts = component.data.custom_id[ID_PREFIX.len()..].split(':');
+    let id = parts.next().and_then(|p| p.parse::<u64>().ok());
+    let answer = parts.next() == Some("yes");
+    let clicker = component.user.id;
+
+    let asker = {
+        let data = ctx.data.read().await;
+        data.get::<ConfirmKey>()
+            .expect("Expected Confirmations in TypeMap.")
+            .pending
+            .get(&id.unwrap_or_default())
+            .map(|(user, _)| *user)
+    };
+    if asker.map_or(false, |user| user != clicker) {
+        let _ = component
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content("Only the moderator who ran the command can answer")
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await;
+        return;
+    }
+    let sender = {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<ConfirmKey>()
+            .expect("Expected Confirmations in TypeMap.")
+            .pending
+            .remove(&id.unwrap_or_default())
+            .map(|(_, sender)| sender)
+    };
+    let text = match &sender {
+        Some(_) if answer => "Confirmed",
+        Some(_) => "Cancelled",
+        None => "This prompt has expired",
+    };
+    let _ = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(text).components(|c| c))
+        })
+        .await;
+    if let Some(sender) = sender {
+        let _ = sender.send(answer);
+    }
+}
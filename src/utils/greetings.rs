@@ -0,0 +1,244 @@
+use crate::utils::{
+    guildconfig::{self, GuildSettings},
+    moderation::guild_name,
+    scheduler,
+};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::user::User;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::fmt;
+use std::str::FromStr;
+use tracing::error;
+
+/// Templates are capped below Discord's 2000 character message limit so
+/// placeholders have room to expand.
+pub const MAX_TEMPLATE_LEN: usize = 1500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Welcome,
+    Farewell,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+    /// The welcome channel.
+    Channel,
+    Dm,
+    Both,
+    Off,
+}
+
+impl FromStr for Delivery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "channel" => Ok(Delivery::Channel),
+            "dm" => Ok(Delivery::Dm),
+            "both" => Ok(Delivery::Both),
+            "off" => Ok(Delivery::Off),
+            _ => Err(format!(
+                "`{}` is not a destination. Choose `channel`, `dm`, `both` or `off`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Delivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Delivery::Channel => "The welcome channel",
+            Delivery::Dm => "DM",
+            Delivery::Both => "The welcome channel and DM",
+            Delivery::Off => "Off",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Embed,
+    Plain,
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Style::Embed => "Embed",
+            Style::Plain => "Plain text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "embed" => Ok(Style::Embed),
+            "plain" | "text" => Ok(Style::Plain),
+            _ => Err(format!("`{}` is not a style. Choose `embed` or `plain`", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Greeting {
+    pub template: String,
+    pub delivery: Delivery,
+    pub style: Style,
+}
+
+impl Greeting {
+    pub fn default_for(kind: Kind) -> Greeting {
+        match kind {
+            Kind::Welcome => Greeting {
+                template: "Welcome {mention} to **{guild}**!\nTo verify head on over to {channel:rules}, read the rules and say `{prefix}verify` to verify!\nHave a Great Time!".to_string(),
+                delivery: Delivery::Both,
+                style: Style::Plain,
+            },
+            Kind::Farewell => Greeting {
+                template: "Sorry you had to leave **{guild}**".to_string(),
+                delivery: Delivery::Dm,
+                style: Style::Embed,
+            },
+        }
+    }
+}
+
+/// The guild's greeting for `kind`, or the default if it was never changed.
+pub fn greeting(settings: &GuildSettings, kind: Kind) -> Greeting {
+    let custom = match kind {
+        Kind::Welcome => &settings.welcome,
+        Kind::Farewell => &settings.farewell,
+    };
+    custom
+        .clone()
+        .unwrap_or_else(|| Greeting::default_for(kind))
+}
+
+pub fn greeting_mut(settings: &mut GuildSettings, kind: Kind) -> &mut Greeting {
+    let custom = match kind {
+        Kind::Welcome => &mut settings.welcome,
+        Kind::Farewell => &mut settings.farewell,
+    };
+    custom.get_or_insert_with(|| Greeting::default_for(kind))
+}
+
+/// How long ago an account was made, in its largest unit.
+fn account_age(seconds: i64) -> String {
+    for (unit, secs) in [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ] {
+        let count = seconds / secs;
+        if count > 0 {
+            return format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" });
+        }
+    }
+    "less than a minute".to_string()
+}
+
+/// Fills in `{user}`, `{mention}`, `{guild}`, `{member_count}`,
+/// `{account_age}`, `{prefix}` and `{channel:<name>}`, where the name is one
+/// of the configured channels. Unknown placeholders are left as they are.
+pub async fn render(ctx: &Context, guild: GuildId, user: &User, template: &str) -> String {
+    let settings = guildconfig::settings(ctx, guild).await;
+    let name = guild_name(ctx, guild).await;
+    let member_count = guild
+        .to_guild_cached(&ctx.cache)
+        .await
+        .map_or_else(|| "?".to_string(), |g| g.member_count.to_string());
+    let age = account_age(scheduler::now() - scheduler::created_at(user.id.0));
+    let prefix = guildconfig::current_prefixes(ctx, Some(guild))
+        .await
+        .remove(0);
+    let channel = |name: &str| -> Option<ChannelId> {
+        match name {
+            "welcome" => settings.welcome_channel,
+            "rules" => settings.rules_channel,
+            "verify" => settings.verify_channel,
+            "role" | "roles" => settings.role_channel,
+            _ => None,
+        }
+    };
+    let placeholder = Regex::new(r"\{(\w+)(?::(\w+))?\}").unwrap();
+    placeholder
+        .replace_all(template, |caps: &Captures| {
+            match (&caps[1], caps.get(2).map(|m| m.as_str())) {
+                ("user", None) => user.tag(),
+                ("mention", None) => format!("<@{}>", user.id.0),
+                ("guild", None) => name.clone(),
+                ("member_count", None) => member_count.clone(),
+                ("account_age", None) => age.clone(),
+                ("prefix", None) => prefix.clone(),
+                ("channel", Some(which)) => channel(which).map_or_else(
+                    || format!("the {} channel", which),
+                    |c| format!("<#{}>", c.0),
+                ),
+                _ => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+async fn send(ctx: &Context, channel: ChannelId, style: Style, text: &str) -> serenity::Result<()> {
+    channel
+        .send_message(&ctx.http, |m| match style {
+            Style::Plain => m.content(text),
+            Style::Embed => m.embed(|e| {
+                e.description(text);
+                e.color(Colour::DARK_GREEN);
+                e
+            }),
+        })
+        .await?;
+    Ok(())
+}
+
+/// Renders `greeting` for `user` and sends it to `channel`. Used by
+/// `welcome test` and `farewell test`.
+pub async fn preview(
+    ctx: &Context,
+    guild: GuildId,
+    user: &User,
+    greeting: &Greeting,
+    channel: ChannelId,
+) -> serenity::Result<()> {
+    let text = render(ctx, guild, user, &greeting.template).await;
+    send(ctx, channel, greeting.style, &text).await
+}
+
+/// Sends the guild's welcome or farewell for `user`. Farewell DMs only
+/// arrive if the user still shares a server with the bot.
+pub async fn deliver(ctx: &Context, guild: GuildId, user: &User, kind: Kind) {
+    let settings = guildconfig::settings(ctx, guild).await;
+    let greeting = greeting(&settings, kind);
+    let (to_channel, to_dm) = match greeting.delivery {
+        Delivery::Channel => (true, false),
+        Delivery::Dm => (false, true),
+        Delivery::Both => (true, true),
+        Delivery::Off => return,
+    };
+    let text = render(ctx, guild, user, &greeting.template).await;
+    if let (true, Some(channel)) = (to_channel, settings.welcome_channel) {
+        if let Err(why) = send(ctx, channel, greeting.style, &text).await {
+            error!("Failed to send {:?} in {}: {:?}", kind, guild, why);
+        }
+    }
+    if to_dm {
+        if let Ok(dm) = user.create_dm_channel(ctx).await {
+            let _ = send(ctx, dm.id, greeting.style, &text).await;
+        }
+    }
+}
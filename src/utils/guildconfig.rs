@@ -0,0 +1,107 @@
+use crate::utils::{
+    antiraid::RaidSettings, automod::AutomodSettings, config::BotConfigKey, greetings::Greeting,
+    storage, verification::VerifySettings, warnings::WarnRule, wordfilter::FilterSettings,
+};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+pub struct GuildConfigKey;
+
+impl TypeMapKey for GuildConfigKey {
+    type Value = GuildConfigStore;
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct GuildSettings {
+    pub welcome_channel: Option<ChannelId>,
+    pub rules_channel: Option<ChannelId>,
+    pub verify_channel: Option<ChannelId>,
+    pub role_channel: Option<ChannelId>,
+    pub modlog_channel: Option<ChannelId>,
+    /// Where alerts that need a moderator's attention are posted.
+    pub staff_channel: Option<ChannelId>,
+    /// The category modmail threads are opened in.
+    pub modmail_category: Option<ChannelId>,
+    pub unverified_role: Option<RoleId>,
+    pub user_role: Option<RoleId>,
+    pub mute_role: Option<RoleId>,
+    pub prefixes: Vec<String>,
+    /// Added to the DM members get when they are punished.
+    pub appeal_message: Option<String>,
+    /// Warning escalation rules, applied after every warning.
+    pub warn_rules: Vec<WarnRule>,
+    pub automod: AutomodSettings,
+    /// Blocked words and patterns.
+    pub filter: FilterSettings,
+    pub raid: RaidSettings,
+    pub verification: VerifySettings,
+    /// Sent when members join and leave. `None` uses the built in text.
+    pub welcome: Option<Greeting>,
+    pub farewell: Option<Greeting>,
+}
+
+pub struct GuildConfigStore {
+    path: PathBuf,
+    guilds: HashMap<u64, GuildSettings>,
+}
+
+impl GuildConfigStore {
+    pub async fn load(path: PathBuf) -> io::Result<GuildConfigStore> {
+        let guilds = storage::load(&path).await?;
+        Ok(GuildConfigStore { path, guilds })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.guilds).await
+    }
+
+    pub fn get(&self, guild: GuildId) -> GuildSettings {
+        self.guilds.get(&guild.0).cloned().unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, guild: GuildId) -> &mut GuildSettings {
+        self.guilds.entry(guild.0).or_default()
+    }
+
+    pub fn prefixes(&self, guild: GuildId) -> &[String] {
+        self.guilds
+            .get(&guild.0)
+            .map(|g| g.prefixes.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Snapshot of a guild's settings, for handlers that only need to read them.
+pub async fn settings(ctx: &Context, guild: GuildId) -> GuildSettings {
+    let data = ctx.data.read().await;
+    data.get::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.")
+        .get(guild)
+}
+
+/// Prefixes that currently work in `guild`: its custom prefixes, or the
+/// configured default when it has none (and in DMs).
+pub async fn current_prefixes(ctx: &Context, guild: Option<GuildId>) -> Vec<String> {
+    let data = ctx.data.read().await;
+    let custom = match guild {
+        Some(g) => data
+            .get::<GuildConfigKey>()
+            .expect("Expected GuildConfig in TypeMap.")
+            .prefixes(g),
+        None => &[],
+    };
+    if custom.is_empty() {
+        vec![data
+            .get::<BotConfigKey>()
+            .expect("Expected BotConfig in TypeMap.")
+            .prefix
+            .clone()]
+    } else {
+        custom.to_vec()
+    }
+}
@@ -0,0 +1,151 @@
+use crate::utils::storage;
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use serenity::model::channel::{GuildChannel, PermissionOverwrite, PermissionOverwriteType};
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use serenity::model::permissions::Permissions;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+/// Channels the bot locked, with what to put back on unlock.
+pub struct LockKey;
+
+impl TypeMapKey for LockKey {
+    type Value = LockStore;
+}
+
+/// The @everyone SEND_MESSAGES overwrite before a lock.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SendState {
+    Allow,
+    Deny,
+    Inherit,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SavedLock {
+    pub guild: GuildId,
+    pub previous: SendState,
+    /// Whether @everyone had an overwrite at all. If not, and the lock's is
+    /// the only change, unlocking removes the overwrite again.
+    pub had_overwrite: bool,
+}
+
+pub struct LockStore {
+    path: PathBuf,
+    channels: HashMap<u64, SavedLock>,
+}
+
+impl LockStore {
+    pub async fn load(path: PathBuf) -> io::Result<LockStore> {
+        let channels = storage::load(&path).await?;
+        Ok(LockStore { path, channels })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.channels).await
+    }
+
+    pub fn get(&self, channel: ChannelId) -> Option<SavedLock> {
+        self.channels.get(&channel.0).copied()
+    }
+
+    pub fn insert(&mut self, channel: ChannelId, saved: SavedLock) {
+        self.channels.insert(channel.0, saved);
+    }
+
+    pub fn remove(&mut self, channel: ChannelId) -> Option<SavedLock> {
+        self.channels.remove(&channel.0)
+    }
+
+    pub fn locked_in(&self, guild: GuildId) -> Vec<ChannelId> {
+        self.channels
+            .iter()
+            .filter(|(_, saved)| saved.guild == guild)
+            .map(|(channel, _)| ChannelId(*channel))
+            .collect()
+    }
+}
+
+fn everyone(guild: GuildId) -> PermissionOverwriteType {
+    // The @everyone role shares the guild's id.
+    PermissionOverwriteType::Role(RoleId(guild.0))
+}
+
+fn everyone_overwrite(channel: &GuildChannel) -> Option<&PermissionOverwrite> {
+    channel
+        .permission_overwrites
+        .iter()
+        .find(|o| o.kind == everyone(channel.guild_id))
+}
+
+/// Denies SEND_MESSAGES to @everyone and returns what was there before.
+pub async fn lock(http: &Http, channel: &GuildChannel) -> serenity::Result<SavedLock> {
+    let current = everyone_overwrite(channel);
+    let (allow, deny) = current.map_or((Permissions::empty(), Permissions::empty()), |o| {
+        (o.allow, o.deny)
+    });
+    let previous = if allow.contains(Permissions::SEND_MESSAGES) {
+        SendState::Allow
+    } else if deny.contains(Permissions::SEND_MESSAGES) {
+        SendState::Deny
+    } else {
+        SendState::Inherit
+    };
+    channel
+        .id
+        .create_permission(
+            http,
+            &PermissionOverwrite {
+                allow: allow - Permissions::SEND_MESSAGES,
+                deny: deny | Permissions::SEND_MESSAGES,
+                kind: everyone(channel.guild_id),
+            },
+        )
+        .await?;
+    Ok(SavedLock {
+        guild: channel.guild_id,
+        previous,
+        had_overwrite: current.is_some(),
+    })
+}
+
+/// Puts SEND_MESSAGES back the way [`lock`] found it. Other permissions
+/// changed on the overwrite in the meantime are kept.
+pub async fn unlock(http: &Http, channel: ChannelId, saved: SavedLock) -> serenity::Result<()> {
+    let channel = match channel.to_channel(http).await?.guild() {
+        Some(c) => c,
+        None => return Ok(()),
+    };
+    let (mut allow, mut deny) = everyone_overwrite(&channel)
+        .map_or((Permissions::empty(), Permissions::empty()), |o| {
+            (o.allow, o.deny)
+        });
+    allow.remove(Permissions::SEND_MESSAGES);
+    deny.remove(Permissions::SEND_MESSAGES);
+    match saved.previous {
+        SendState::Allow => allow.insert(Permissions::SEND_MESSAGES),
+        SendState::Deny => deny.insert(Permissions::SEND_MESSAGES),
+        SendState::Inherit => {}
+    }
+    if !saved.had_overwrite && allow.is_empty() && deny.is_empty() {
+        channel
+            .id
+            .delete_permission(http, everyone(saved.guild))
+            .await
+    } else {
+        channel
+            .id
+            .create_permission(
+                http,
+                &PermissionOverwrite {
+                    allow,
+                    deny,
+                    kind: everyone(saved.guild),
+                },
+            )
+            .await
+    }
+}
@@ -1,3 +1,23 @@
+pub mod antiraid;
+pub mod appeals;
+pub mod automod;
+pub mod cases;
 pub mod client;
+pub mod config;
+pub mod confirm;
+pub mod greetings;
+pub mod guildconfig;
+pub mod locks;
+pub mod moderation;
+pub mod modlog;
+pub mod modmail;
+pub mod reactionroles;
+pub mod rolemenus;
+pub mod scheduler;
+pub mod stats;
+pub mod storage;
 pub mod timeparser;
 pub mod uptimer;
+pub mod verification;
+pub mod warnings;
+pub mod wordfilter;
@@ -0,0 +1,505 @@
+use crate::utils::{
+    appeals,
+    cases::{self, Case, CaseAction},
+    guildconfig,
+    scheduler::{self, Action, Scheduled, SchedulerKey},
+    timeparser::HumanDuration,
+};
+use serenity::model::guild::{Member, Role};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::model::permissions::Permissions;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Shared by the moderation commands and the automatic actions (warning
+/// escalation, automod) so both apply and record actions the same way.
+#[derive(Debug)]
+pub enum ActionError {
+    NoMuteRole,
+    AlreadyMuted,
+    /// The action isn't allowed against this member. Explains why.
+    Refused(String),
+    /// A softban's ban went through but its unban didn't. The ban was
+    /// recorded as this case.
+    StillBanned(u64, SerenityError),
+    Discord(SerenityError),
+    Storage(std::io::Error),
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::NoMuteRole => write!(
+                f,
+                "No mute role is configured. Set one with `config set mute`"
+            ),
+            ActionError::AlreadyMuted => write!(f, "Aldready Has Role"),
+            ActionError::Refused(why) => write!(f, "{}", why),
+            ActionError::StillBanned(case, why) => write!(
+                f,
+                "They were banned, but the unban failed ({}), so they are still banned. Case #{} records the ban; unban them by hand",
+                why, case
+            ),
+            ActionError::Discord(why) => write!(f, "Discord refused: {}", why),
+            ActionError::Storage(why) => write!(f, "Couldn't save changes: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+impl From<SerenityError> for ActionError {
+    fn from(why: SerenityError) -> Self {
+        ActionError::Discord(why)
+    }
+}
+
+impl From<std::io::Error> for ActionError {
+    fn from(why: std::io::Error) -> Self {
+        ActionError::Storage(why)
+    }
+}
+
+/// The permission the moderator needs for `action`, and the one the bot needs.
+fn required(action: CaseAction) -> ((Permissions, &'static str), (Permissions, &'static str)) {
+    match action {
+        CaseAction::Kick => (
+            (Permissions::KICK_MEMBERS, "Kick Members"),
+            (Permissions::KICK_MEMBERS, "Kick Members"),
+        ),
+        CaseAction::Ban | CaseAction::Tempban | CaseAction::Softban | CaseAction::Unban => (
+            (Permissions::BAN_MEMBERS, "Ban Members"),
+            (Permissions::BAN_MEMBERS, "Ban Members"),
+        ),
+        CaseAction::Mute | CaseAction::Unmute => (
+            (Permissions::KICK_MEMBERS, "Kick Members"),
+            (Permissions::MANAGE_ROLES, "Manage Roles"),
+        ),
+        CaseAction::Warn => (
+            (Permissions::KICK_MEMBERS, "Kick Members"),
+            (Permissions::empty(), ""),
+        ),
+    }
+}
+
+fn top_position(member: &Member, roles: &HashMap<RoleId, Role>) -> i64 {
+    member
+        .roles
+        .iter()
+        .filter_map(|r| roles.get(r))
+        .map(|r| r.position)
+        .max()
+        .unwrap_or(0)
+}
+
+fn permissions(
+    member: &Member,
+    guild: GuildId,
+    owner: UserId,
+    roles: &HashMap<RoleId, Role>,
+) -> Permissions {
+    if member.user.id == owner {
+        return Permissions::all();
+    }
+    // The @everyone role shares the guild's id.
+    let granted = member
+        .roles
+        .iter()
+        .chain(std::iter::once(&RoleId(guild.0)))
+        .filter_map(|r| roles.get(r))
+        .fold(Permissions::empty(), |p, r| p | r.permissions);
+    if granted.contains(Permissions::ADMINISTRATOR) {
+        Permissions::all()
+    } else {
+        granted
+    }
+}
+
+async fn owner_and_roles(
+    ctx: &Context,
+    guild: GuildId,
+) -> serenity::Result<(UserId, HashMap<RoleId, Role>)> {
+    match guild.to_guild_cached(&ctx.cache).await {
+        Some(g) => Ok((g.owner_id, g.roles)),
+        None => {
+            let g = guild.to_partial_guild(&ctx.http).await?;
+            Ok((g.owner_id, g.roles))
+        }
+    }
+}
+
+/// Checks that `moderator` may let members pick `picked` for themselves
+/// through reaction roles or a role menu. Each role has to sit below the
+/// moderator's highest role, unless they own the server, and below the bot's.
+pub async fn check_assignable(
+    ctx: &Context,
+    guild: GuildId,
+    moderator: UserId,
+    picked: &[RoleId],
+) -> Result<(), ActionError> {
+    let (owner, roles) = owner_and_roles(ctx, guild).await?;
+    let bot = ctx.cache.current_user_id().await;
+    let bot_top = top_position(&guild.member(ctx, bot).await?, &roles);
+    let moderator_top = if moderator == owner {
+        None
+    } else {
+        Some(top_position(&guild.member(ctx, moderator).await?, &roles))
+    };
+    for role in picked {
+        let position = match roles.get(role) {
+            Some(r) => r.position,
+            None => {
+                return Err(ActionError::Refused(format!(
+                    "<@&{}> isn't a role in this server",
+                    role.0
+                )))
+            }
+        };
+        if moderator_top.map_or(false, |top| position >= top) {
+            return Err(ActionError::Refused(format!(
+                "<@&{}> is at or above your highest role",
+                role.0
+            )));
+        }
+        if position >= bot_top {
+            return Err(ActionError::Refused(format!(
+                "<@&{}> is at or above my highest role",
+                role.0
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `moderator` may take `action` against `target` before anything
+/// happens: nobody acts on themselves, the bot or the owner, both the
+/// moderator and the bot need the right permission, and both need a higher
+/// top role than the target. Actions the bot takes on its own skip the
+/// moderator checks.
+pub async fn check_target(
+    ctx: &Context,
+    guild: GuildId,
+    moderator: UserId,
+    target: UserId,
+    action: CaseAction,
+) -> Result<(), ActionError> {
+    let refuse = |why: &str| Err(ActionError::Refused(why.to_string()));
+    let bot = ctx.cache.current_user_id().await;
+    let automatic = moderator == bot;
+    if target == moderator && !automatic {
+        return refuse("You can't do that to yourself");
+    }
+    if target == bot {
+        return refuse("I can't do that to myself");
+    }
+    let (owner, roles) = owner_and_roles(ctx, guild).await?;
+    if target == owner {
+        return refuse("The server owner can't be moderated");
+    }
+    let ((moderator_needs, moderator_name), (bot_needs, bot_name)) = required(action);
+    let bot_member = guild.member(ctx, bot).await?;
+    if !permissions(&bot_member, guild, owner, &roles).contains(bot_needs) {
+        return refuse(&format!("I need the {} permission for that", bot_name));
+    }
+    let moderator_member = if automatic {
+        None
+    } else {
+        let member = guild.member(ctx, moderator).await?;
+        if !permissions(&member, guild, owner, &roles).contains(moderator_needs) {
+            return refuse(&format!(
+                "You need the {} permission for that",
+                moderator_name
+            ));
+        }
+        Some(member)
+    };
+    // Users who aren't in the server can still be banned by id.
+    let target_member = match guild.member(ctx, target).await {
+        Ok(m) => m,
+        Err(_) => return Ok(()),
+    };
+    let target_top = top_position(&target_member, &roles);
+    if let Some(member) = moderator_member {
+        if member.user.id != owner && top_position(&member, &roles) <= target_top {
+            return refuse("Their highest role is at or above yours");
+        }
+    }
+    if top_position(&bot_member, &roles) <= target_top {
+        return refuse("Their highest role is at or above mine");
+    }
+    Ok(())
+}
+
+pub async fn kick(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: String,
+) -> Result<Case, ActionError> {
+    check_target(ctx, guild, moderator, user, CaseAction::Kick).await?;
+    guild.kick_with_reason(&ctx.http, user, &reason).await?;
+    let case = Case::new(CaseAction::Kick, user, moderator, Some(reason));
+    Ok(cases::open(&ctx.http, &ctx.data, guild, case).await)
+}
+
+/// Bans `user`, deleting their messages from the last `delete_days` days (at
+/// most 7). Works for users who are not in the guild. A pending tempban is
+/// cancelled so it doesn't lift the new ban.
+pub async fn ban(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: String,
+    delete_days: u8,
+) -> Result<Case, ActionError> {
+    check_target(ctx, guild, moderator, user, CaseAction::Ban).await?;
+    guild
+        .ban_with_reason(&ctx.http, user, delete_days, &reason)
+        .await?;
+    {
+        let mut data = ctx.data.write().await;
+        let schedule = data
+            .get_mut::<SchedulerKey>()
+            .expect("Expected Scheduler in TypeMap.");
+        if schedule.take_ban(guild, user).is_some() {
+            schedule.save().await?;
+        }
+    }
+    let case = Case::new(CaseAction::Ban, user, moderator, Some(reason));
+    Ok(cases::open(&ctx.http, &ctx.data, guild, case).await)
+}
+
+/// Bans to delete the member's recent messages, then unbans right away so
+/// they can rejoin.
+pub async fn softban(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: String,
+    delete_days: u8,
+) -> Result<Case, ActionError> {
+    check_target(ctx, guild, moderator, user, CaseAction::Softban).await?;
+    guild
+        .ban_with_reason(&ctx.http, user, delete_days, &reason)
+        .await?;
+    if let Err(why) = guild.unban(&ctx.http, user).await {
+        let reason = format!("{} (softban, unban failed)", reason);
+        let case = Case::new(CaseAction::Ban, user, moderator, Some(reason));
+        let case = cases::open(&ctx.http, &ctx.data, guild, case).await;
+        return Err(ActionError::StillBanned(case.number, why));
+    }
+    let case = Case::new(CaseAction::Softban, user, moderator, Some(reason));
+    Ok(cases::open(&ctx.http, &ctx.data, guild, case).await)
+}
+
+/// Lifts a ban and cancels any pending tempban. Returns `None` if the user
+/// wasn't banned.
+pub async fn unban(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: Option<String>,
+) -> Result<Option<Case>, ActionError> {
+    check_target(ctx, guild, moderator, user, CaseAction::Unban).await?;
+    if !scheduler::lift_ban(&ctx.http, guild, user).await? {
+        return Ok(None);
+    }
+    {
+        let mut data = ctx.data.write().await;
+        let schedule = data
+            .get_mut::<SchedulerKey>()
+            .expect("Expected Scheduler in TypeMap.");
+        if schedule.take_ban(guild, user).is_some() {
+            schedule.save().await?;
+        }
+    }
+    let case = Case::new(CaseAction::Unban, user, moderator, reason);
+    Ok(Some(cases::open(&ctx.http, &ctx.data, guild, case).await))
+}
+
+/// Bans and schedules the unban. The expiry notice is posted in `notice`.
+pub async fn tempban(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: String,
+    duration: HumanDuration,
+    notice: ChannelId,
+) -> Result<Case, ActionError> {
+    check_target(ctx, guild, moderator, user, CaseAction::Tempban).await?;
+    guild.ban_with_reason(&ctx.http, user, 0, &reason).await?;
+    let expires_at = scheduler::now() + duration.whole_seconds();
+    {
+        let mut data = ctx.data.write().await;
+        let schedule = data
+            .get_mut::<SchedulerKey>()
+            .expect("Expected Scheduler in TypeMap.");
+        schedule.add(Scheduled {
+            guild,
+            user,
+            action: Action::Unban { channel: notice },
+            expires_at,
+            attempts: 0,
+        });
+        schedule.save().await?;
+    }
+    let case = Case::new(CaseAction::Tempban, user, moderator, Some(reason)).expires(expires_at);
+    Ok(cases::open(&ctx.http, &ctx.data, guild, case).await)
+}
+
+/// Gives the member the configured mute role. The scheduler lifts the mute,
+/// even across restarts.
+pub async fn mute(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: Option<String>,
+    duration: HumanDuration,
+) -> Result<Case, ActionError> {
+    check_target(ctx, guild, moderator, user, CaseAction::Mute).await?;
+    let role = guildconfig::settings(ctx, guild)
+        .await
+        .mute_role
+        .ok_or(ActionError::NoMuteRole)?;
+    let mut member = guild.member(ctx, user).await?;
+    if member.roles.contains(&role) {
+        return Err(ActionError::AlreadyMuted);
+    }
+    member.add_role(&ctx.http, role).await?;
+    let expires_at = scheduler::now() + duration.whole_seconds();
+    {
+        let mut data = ctx.data.write().await;
+        let schedule = data
+            .get_mut::<SchedulerKey>()
+            .expect("Expected Scheduler in TypeMap.");
+        schedule.add(Scheduled {
+            guild,
+            user,
+            action: Action::Unmute { role },
+            expires_at,
+            attempts: 0,
+        });
+        schedule.save().await?;
+    }
+    let case = Case::new(CaseAction::Mute, user, moderator, reason).expires(expires_at);
+    Ok(cases::open(&ctx.http, &ctx.data, guild, case).await)
+}
+
+pub async fn guild_name(ctx: &Context, guild: GuildId) -> String {
+    guild
+        .to_guild_cached(&ctx.cache)
+        .await
+        .map_or_else(|| "the server".to_string(), |g| g.name)
+}
+
+/// What the member is told before an action is taken against them.
+pub struct Notice {
+    pub action: CaseAction,
+    pub reason: Option<String>,
+    pub duration: Option<HumanDuration>,
+}
+
+/// Removes a `--silent` flag from a reason. Returns the reason and whether
+/// the flag was there.
+pub fn take_silent(reason: &str) -> (String, bool) {
+    let silent = reason.split_whitespace().any(|w| w == "--silent");
+    let reason = reason
+        .split_whitespace()
+        .filter(|w| *w != "--silent")
+        .collect::<Vec<&str>>()
+        .join(" ");
+    (reason, silent)
+}
+
+/// Runs [`check_target`], then DMs the member about the action unless
+/// `silent`. The DM goes out first since it can't be delivered once a kicked
+/// or banned member shares no server with the bot. Returns whether the DM was
+/// delivered, or `None` when silent.
+pub async fn check_and_notify(
+    ctx: &Context,
+    guild: GuildId,
+    moderator: UserId,
+    target: UserId,
+    notice: &Notice,
+    silent: bool,
+) -> Result<Option<bool>, ActionError> {
+    check_target(ctx, guild, moderator, target, notice.action).await?;
+    if silent {
+        return Ok(None);
+    }
+    let name = guild_name(ctx, guild).await;
+    let settings = guildconfig::settings(ctx, guild).await;
+    let mut appeal = settings.appeal_message.clone();
+    if matches!(notice.action, CaseAction::Ban | CaseAction::Tempban)
+        && appeals::review_channel(&settings).is_some()
+    {
+        let prefix = guildconfig::current_prefixes(ctx, None).await.remove(0);
+        let how = format!(
+            "Send me `{}appeal {} <message>` to appeal this ban",
+            prefix, guild.0
+        );
+        appeal = Some(match appeal {
+            Some(text) => format!("{}\n{}", text, how),
+            None => how,
+        });
+    }
+    let done = match notice.action {
+        CaseAction::Kick => "kicked from",
+        CaseAction::Ban => "banned from",
+        CaseAction::Tempban => "temporarily banned from",
+        CaseAction::Softban => "softbanned from",
+        CaseAction::Mute => "muted in",
+        _ => "warned in",
+    };
+    let dm = match target.create_dm_channel(ctx).await {
+        Ok(dm) => dm,
+        Err(_) => return Ok(Some(false)),
+    };
+    let sent = dm
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("You were {} {}", done, name));
+                e.field(
+                    "Reason",
+                    notice.reason.as_deref().unwrap_or("No reason given"),
+                    false,
+                );
+                if let Some(duration) = notice.duration {
+                    e.field("Duration", duration, true);
+                }
+                if let Some(appeal) = &appeal {
+                    e.field("Appeal", appeal, false);
+                }
+                e.color(Colour::RED);
+                e
+            })
+        })
+        .await;
+    Ok(Some(sent.is_ok()))
+}
+
+/// How a moderator is told whether the member got the DM.
+pub fn dm_note(delivered: Option<bool>) -> &'static str {
+    match delivered {
+        Some(true) => "They were told by DM",
+        Some(false) => "Couldn't DM them",
+        None => "No DM was sent",
+    }
+}
+
+/// Sends the user a direct message. Returns whether it was delivered; members
+/// often have DMs closed, which is not an error.
+pub async fn notify(ctx: &Context, user: UserId, text: String) -> bool {
+    match user.create_dm_channel(ctx).await {
+        Ok(dm) => dm.say(&ctx.http, text).await.is_ok(),
+        Err(_) => false,
+    }
+}
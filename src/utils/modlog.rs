@@ -0,0 +1,216 @@
+use crate::utils::{
+    cases::{self, Case, CaseAction},
+    guildconfig::GuildConfigKey,
+    scheduler,
+};
+use serenity::http::Http;
+use serenity::model::guild::{AuditLogEntry, MemberAction};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::time::Duration;
+use tracing::error;
+
+/// Audit log entries older than this are not matched to gateway events.
+const AUDIT_LOG_WINDOW: i64 = 30;
+/// Discord can send the gateway event before the audit log entry exists.
+const AUDIT_LOG_DELAY: Duration = Duration::from_secs(2);
+
+async fn channel(data: &RwLock<TypeMap>, guild: GuildId) -> Option<ChannelId> {
+    let data = data.read().await;
+    data.get::<GuildConfigKey>()
+        .expect("Expected GuildConfig in TypeMap.")
+        .get(guild)
+        .modlog_channel
+}
+
+pub async fn post_case(http: &Http, data: &RwLock<TypeMap>, guild: GuildId, case: &Case) {
+    if let Some(log) = channel(data, guild).await {
+        let sent = log
+            .send_message(http, |m| {
+                m.embed(|e| {
+                    case.embed(e);
+                    e
+                })
+            })
+            .await;
+        if let Err(why) = sent {
+            error!(
+                "Failed to post case #{} to the mod log: {:?}",
+                case.number, why
+            );
+        }
+    }
+}
+
+pub async fn post_purge(
+    http: &Http,
+    data: &RwLock<TypeMap>,
+    guild: GuildId,
+    moderator: UserId,
+    purged: ChannelId,
+    count: usize,
+) {
+    if let Some(log) = channel(data, guild).await {
+        let sent = log
+            .send_message(http, |m| {
+                m.embed(|e| {
+                    e.title("Purge");
+                    e.field("Channel", format!("<#{}>", purged.0), true);
+                    e.field("Moderator", format!("<@{}>", moderator.0), true);
+                    e.field("Messages", count, true);
+                    e.field("Date", format!("<t:{}:f>", scheduler::now()), false);
+                    e.color(Colour::DARK_GREY);
+                    e
+                })
+            })
+            .await;
+        if let Err(why) = sent {
+            error!("Failed to post purge to the mod log: {:?}", why);
+        }
+    }
+}
+
+/// Logs a lock, unlock or slowmode change.
+pub async fn post_channels(
+    http: &Http,
+    data: &RwLock<TypeMap>,
+    guild: GuildId,
+    title: &str,
+    moderator: UserId,
+    channels: &[ChannelId],
+    reason: Option<&str>,
+) {
+    if let Some(log) = channel(data, guild).await {
+        let mut listed = channels
+            .iter()
+            .map(|c| format!("<#{}>", c.0))
+            .collect::<Vec<String>>()
+            .join(", ");
+        if listed.len() > 1000 {
+            listed = format!("{} channels", channels.len());
+        }
+        let sent = log
+            .send_message(http, |m| {
+                m.embed(|e| {
+                    e.title(title);
+                    e.field("Channels", listed, false);
+                    e.field("Moderator", format!("<@{}>", moderator.0), true);
+                    e.field("Reason", reason.unwrap_or("No reason given"), true);
+                    e.field("Date", format!("<t:{}:f>", scheduler::now()), false);
+                    e.color(Colour::DARK_GREY);
+                    e
+                })
+            })
+            .await;
+        if let Err(why) = sent {
+            error!("Failed to post {} to the mod log: {:?}", title, why);
+        }
+    }
+}
+
+/// Logs a message the bot deleted on its own, such as an automod hit.
+pub async fn post_deletion(
+    http: &Http,
+    data: &RwLock<TypeMap>,
+    guild: GuildId,
+    author: UserId,
+    deleted_in: ChannelId,
+    reason: &str,
+    content: &str,
+) {
+    if let Some(log) = channel(data, guild).await {
+        // Embed field values are capped at 1024 characters.
+        let mut content = content.chars().take(1000).collect::<String>();
+        if content.is_empty() {
+            content.push_str("*No text*");
+        }
+        let sent = log
+            .send_message(http, |m| {
+                m.embed(|e| {
+                    e.title("Message Deleted");
+                    e.field("User", format!("<@{}>", author.0), true);
+                    e.field("Channel", format!("<#{}>", deleted_in.0), true);
+                    e.field("Reason", reason, false);
+                    e.field("Content", content, false);
+                    e.field("Date", format!("<t:{}:f>", scheduler::now()), false);
+                    e.color(Colour::DARK_GREY);
+                    e
+                })
+            })
+            .await;
+        if let Err(why) = sent {
+            error!("Failed to post deletion to the mod log: {:?}", why);
+        }
+    }
+}
+
+/// Logs a nickname the bot reset because it matched the word filter.
+pub async fn post_nickname(
+    http: &Http,
+    data: &RwLock<TypeMap>,
+    guild: GuildId,
+    member: UserId,
+    nickname: &str,
+    matched: &str,
+) {
+    if let Some(log) = channel(data, guild).await {
+        let sent = log
+            .send_message(http, |m| {
+                m.embed(|e| {
+                    e.title("Nickname Reset");
+                    e.field("User", format!("<@{}>", member.0), true);
+                    e.field("Nickname", nickname, true);
+                    e.field("Reason", format!("Filter: matched `{}`", matched), false);
+                    e.field("Date", format!("<t:{}:f>", scheduler::now()), false);
+                    e.color(Colour::DARK_GREY);
+                    e
+                })
+            })
+            .await;
+        if let Err(why) = sent {
+            error!("Failed to post nickname reset to the mod log: {:?}", why);
+        }
+    }
+}
+
+/// The newest audit log entry for `action` against `target`, if it happened
+/// in the last few seconds.
+async fn recent_entry(
+    http: &Http,
+    guild: GuildId,
+    action: MemberAction,
+    target: UserId,
+) -> Option<AuditLogEntry> {
+    tokio::time::sleep(AUDIT_LOG_DELAY).await;
+    let logs = guild
+        .audit_logs(http, Some(action as u8), None, None, Some(10))
+        .await
+        .ok()?;
+    let now = scheduler::now();
+    logs.entries.into_iter().find(|entry| {
+        entry.target_id == Some(target.0)
+            && now - scheduler::created_at(entry.id.0) <= AUDIT_LOG_WINDOW
+    })
+}
+
+/// Opens a case for a kick, ban or unban seen on the gateway. Actions taken
+/// by the bot itself are skipped since the command already opened a case.
+/// Members who leave on their own have no kick entry and are ignored.
+pub async fn record_external(ctx: &Context, guild: GuildId, action: CaseAction, target: UserId) {
+    let audit_action = match action {
+        CaseAction::Kick => MemberAction::Kick,
+        CaseAction::Ban => MemberAction::BanAdd,
+        CaseAction::Unban => MemberAction::BanRemove,
+        _ => return,
+    };
+    let entry = match recent_entry(&ctx.http, guild, audit_action, target).await {
+        Some(e) => e,
+        None => return,
+    };
+    if entry.user_id == ctx.cache.current_user_id().await {
+        return;
+    }
+    let case = Case::new(action, target, entry.user_id, entry.reason);
+    cases::open(&ctx.http, &ctx.data, guild, case).await;
+}
@@ -0,0 +1,471 @@
+use crate::utils::{guildconfig, moderation, scheduler, storage};
+use serde::{Deserialize, Serialize};
+use serenity::model::channel::{ChannelType, Message, ReactionType};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::model::user::User;
+use serenity::prelude::*;
+use serenity::utils::Colour;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::error;
+
+/// Open modmail threads, keyed by their staff channel.
+pub struct ModmailKey;
+
+/// How long a DM waits between checks while another DM from the same user is
+/// still opening their thread.
+const OPENING_POLL: Duration = Duration::from_millis(250);
+
+impl TypeMapKey for ModmailKey {
+    type Value = ModmailStore;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub author: UserId,
+    /// Whether this was a staff reply rather than the member's DM.
+    pub staff: bool,
+    pub content: String,
+    pub attachments: Vec<String>,
+    /// Unix timestamp in seconds.
+    pub at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Thread {
+    pub guild: GuildId,
+    pub user: UserId,
+    pub opened_at: i64,
+    pub messages: Vec<Entry>,
+}
+
+/// What is written to `transcripts/<channel>.json` when a thread closes.
+#[derive(Serialize)]
+struct Transcript<'a> {
+    channel: ChannelId,
+    closed_by: UserId,
+    closed_at: i64,
+    reason: Option<&'a str>,
+    #[serde(flatten)]
+    thread: &'a Thread,
+}
+
+pub struct ModmailStore {
+    path: PathBuf,
+    transcripts: PathBuf,
+    threads: HashMap<u64, Thread>,
+    /// Guild and user pairs whose thread is being opened right now.
+    opening: HashSet<(u64, u64)>,
+}
+
+impl ModmailStore {
+    pub async fn load(path: PathBuf, transcripts: PathBuf) -> io::Result<ModmailStore> {
+        let threads = storage::load(&path).await?;
+        Ok(ModmailStore {
+            path,
+            transcripts,
+            threads,
+            opening: HashSet::new(),
+        })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.threads).await
+    }
+
+    pub fn get(&self, channel: ChannelId) -> Option<&Thread> {
+        self.threads.get(&channel.0)
+    }
+
+    /// Whether `channel` holds an open thread from `guild`.
+    pub fn is_thread(&self, guild: GuildId, channel: ChannelId) -> bool {
+        self.get(channel).map_or(false, |t| t.guild == guild)
+    }
+
+    /// The channel of the user's open thread in `guild`.
+    pub fn channel_of(&self, guild: GuildId, user: UserId) -> Option<ChannelId> {
+        self.threads
+            .iter()
+            .find(|(_, t)| t.guild == guild && t.user == user)
+            .map(|(channel, _)| ChannelId(*channel))
+    }
+
+    pub fn guilds_of(&self, user: UserId) -> Vec<GuildId> {
+        self.threads
+            .values()
+            .filter(|t| t.user == user)
+            .map(|t| t.guild)
+            .collect()
+    }
+
+    fn record(&mut self, channel: ChannelId, entry: Entry) {
+        if let Some(thread) = self.threads.get_mut(&channel.0) {
+            thread.messages.push(entry);
+        }
+    }
+}
+
+async fn save(ctx: &Context) {
+    let data = ctx.data.read().await;
+    let store = data
+        .get::<ModmailKey>()
+        .expect("Expected Modmail in TypeMap.");
+    if let Err(why) = store.save().await {
+        error!("Failed to save modmail: {}", why);
+    }
+}
+
+/// Servers the user shares with the bot that take modmail, and servers they
+/// already have a thread in.
+async fn reachable(ctx: &Context, user: UserId) -> Vec<GuildId> {
+    let mut guilds = {
+        let data = ctx.data.read().await;
+        data.get::<ModmailKey>()
+            .expect("Expected Modmail in TypeMap.")
+            .guilds_of(user)
+    };
+    for guild in ctx.cache.guilds().await {
+        if guilds.contains(&guild)
+            || guildconfig::settings(ctx, guild)
+                .await
+                .modmail_category
+                .is_none()
+        {
+            continue;
+        }
+        if guild.member(ctx, user).await.is_ok() {
+            guilds.push(guild);
+        }
+    }
+    guilds
+}
+
+fn attachments(msg: &Message) -> Vec<String> {
+    msg.attachments.iter().map(|a| a.url.clone()).collect()
+}
+
+/// Creates a staff channel for the user in the guild's modmail category.
+async fn create_channel(
+    ctx: &Context,
+    guild: GuildId,
+    user: &User,
+) -> serenity::Result<Option<ChannelId>> {
+    let settings = guildconfig::settings(ctx, guild).await;
+    let category = match settings.modmail_category {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+    let channel = guild
+        .create_channel(&ctx.http, |c| {
+            c.name(format!("{}-{:04}", user.name, user.discriminator))
+                .kind(ChannelType::Text)
+                .category(category)
+                .topic(format!("Modmail with {} ({})", user.tag(), user.id.0))
+        })
+        .await?;
+    let prefix = guildconfig::current_prefixes(ctx, Some(guild))
+        .await
+        .remove(0);
+    channel
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("New Modmail Thread");
+                e.author(|a| a.name(user.tag()).icon_url(user.face()));
+                e.field("User", format!("<@{}> ({})", user.id.0, user.id.0), true);
+                e.field(
+                    "Account Created",
+                    format!("<t:{}:R>", scheduler::created_at(user.id.0)),
+                    true,
+                );
+                e.description(format!(
+                    "Answer with `{}reply <message>` and close with `{}close [reason]`. Other messages here stay between staff",
+                    prefix, prefix
+                ));
+                e.color(Colour::BLURPLE);
+                e
+            })
+        })
+        .await?;
+    Ok(Some(channel.id))
+}
+
+/// The user's thread in `guild`, or `None` once this DM has claimed the job
+/// of opening it. A DM that arrives while another one is opening the thread
+/// waits for it, so quick messages don't each get a channel.
+async fn claim(ctx: &Context, guild: GuildId, user: UserId) -> Option<ChannelId> {
+    loop {
+        {
+            let mut data = ctx.data.write().await;
+            let store = data
+                .get_mut::<ModmailKey>()
+                .expect("Expected Modmail in TypeMap.");
+            if let Some(channel) = store.channel_of(guild, user) {
+                return Some(channel);
+            }
+            if store.opening.insert((guild.0, user.0)) {
+                return None;
+            }
+        }
+        tokio::time::sleep(OPENING_POLL).await;
+    }
+}
+
+/// Opens a thread after [`claim`] and gives up the claim, whether or not the
+/// channel could be made.
+async fn open(ctx: &Context, guild: GuildId, user: &User) -> serenity::Result<Option<ChannelId>> {
+    let created = create_channel(ctx, guild, user).await;
+    let mut data = ctx.data.write().await;
+    let store = data
+        .get_mut::<ModmailKey>()
+        .expect("Expected Modmail in TypeMap.");
+    store.opening.remove(&(guild.0, user.id.0));
+    if let Ok(Some(channel)) = created {
+        store.threads.insert(
+            channel.0,
+            Thread {
+                guild,
+                user: user.id,
+                opened_at: scheduler::now(),
+                messages: Vec::new(),
+            },
+        );
+    }
+    created
+}
+
+/// Relays a DM to the staff of a server the user shares with the bot,
+/// opening a thread on the first message. Users in more than one such server
+/// start their message with the server id.
+pub async fn relay_dm(ctx: &Context, msg: &Message) {
+    if msg.author.bot || msg.guild_id.is_some() {
+        return;
+    }
+    // Commands sent in DMs, like `appeal`, are left to the framework.
+    let prefixes = guildconfig::current_prefixes(ctx, None).await;
+    if prefixes.iter().any(|p| msg.content.starts_with(p.as_str())) {
+        return;
+    }
+    let guilds = reachable(ctx, msg.author.id).await;
+    let text = msg.content.trim();
+    let first = text.split_whitespace().next().unwrap_or_default();
+    let (guild, content) = match first.parse::<u64>().ok().map(GuildId) {
+        Some(g) if guilds.contains(&g) => (g, text[first.len()..].trim()),
+        _ if guilds.len() == 1 => (guilds[0], text),
+        _ if guilds.is_empty() => return,
+        _ => {
+            let mut servers = String::new();
+            for guild in &guilds {
+                servers.push_str(&format!(
+                    "\n`{}` {}",
+                    guild.0,
+                    moderation::guild_name(ctx, *guild).await
+                ));
+            }
+            let _ = msg
+                .channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "Which server is this for? Start your message with the server id:{}",
+                        servers
+                    ),
+                )
+                .await;
+            return;
+        }
+    };
+    if content.is_empty() && msg.attachments.is_empty() {
+        return;
+    }
+    let name = moderation::guild_name(ctx, guild).await;
+    let channel = match claim(ctx, guild, msg.author.id).await {
+        Some(c) => c,
+        None => match open(ctx, guild, &msg.author).await {
+            Ok(Some(c)) => {
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Your message was sent to the staff of **{}**. Their replies will arrive here",
+                            name
+                        ),
+                    )
+                    .await;
+                c
+            }
+            Ok(None) => return,
+            Err(why) => {
+                error!("Failed to open a modmail thread in {}: {:?}", guild, why);
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!("Couldn't reach the staff of **{}**", name),
+                    )
+                    .await;
+                return;
+            }
+        },
+    };
+    let files = attachments(msg);
+    let sent = channel
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.author(|a| a.name(msg.author.tag()).icon_url(msg.author.face()));
+                e.description(content);
+                if !files.is_empty() {
+                    e.field("Attachments", files.join("\n"), false);
+                }
+                e.color(Colour::DARK_GREEN);
+                e
+            })
+        })
+        .await;
+    if let Err(why) = sent {
+        error!("Failed to relay modmail to {}: {:?}", channel, why);
+        let _ = msg
+            .channel_id
+            .say(
+                &ctx.http,
+                format!("Couldn't reach the staff of **{}**", name),
+            )
+            .await;
+        return;
+    }
+    {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<ModmailKey>()
+            .expect("Expected Modmail in TypeMap.")
+            .record(
+                channel,
+                Entry {
+                    author: msg.author.id,
+                    staff: false,
+                    content: content.to_string(),
+                    attachments: files,
+                    at: scheduler::now(),
+                },
+            );
+    }
+    save(ctx).await;
+    let _ = msg
+        .react(&ctx.http, ReactionType::Unicode("✅".to_string()))
+        .await;
+}
+
+/// Sends a staff reply from `msg` to the user of the thread in its channel.
+/// Returns whether the DM was delivered, or `None` outside a thread.
+pub async fn reply(ctx: &Context, msg: &Message, content: &str) -> Option<bool> {
+    let thread = {
+        let data = ctx.data.read().await;
+        data.get::<ModmailKey>()
+            .expect("Expected Modmail in TypeMap.")
+            .get(msg.channel_id)
+            .cloned()
+    }?;
+    let name = moderation::guild_name(ctx, thread.guild).await;
+    let files = attachments(msg);
+    let delivered = match thread.user.create_dm_channel(ctx).await {
+        Ok(dm) => dm
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.author(|a| {
+                        a.name(format!("{} | {}", msg.author.tag(), name))
+                            .icon_url(msg.author.face())
+                    });
+                    e.description(content);
+                    if !files.is_empty() {
+                        e.field("Attachments", files.join("\n"), false);
+                    }
+                    if let Some(image) = msg.attachments.iter().find(|a| a.width.is_some()) {
+                        e.image(&image.url);
+                    }
+                    e.color(Colour::BLURPLE);
+                    e
+                })
+            })
+            .await
+            .is_ok(),
+        Err(_) => false,
+    };
+    if delivered {
+        {
+            let mut data = ctx.data.write().await;
+            data.get_mut::<ModmailKey>()
+                .expect("Expected Modmail in TypeMap.")
+                .record(
+                    msg.channel_id,
+                    Entry {
+                        author: msg.author.id,
+                        staff: true,
+                        content: content.to_string(),
+                        attachments: files,
+                        at: scheduler::now(),
+                    },
+                );
+        }
+        save(ctx).await;
+    }
+    Some(delivered)
+}
+
+/// Closes the thread in `channel`: saves its transcript, tells the user and
+/// deletes the channel. Returns the closed thread, or `None` if `channel`
+/// isn't one.
+pub async fn close(
+    ctx: &Context,
+    channel: ChannelId,
+    closer: UserId,
+    reason: Option<&str>,
+) -> io::Result<Option<Thread>> {
+    let (thread, transcripts) = {
+        let data = ctx.data.read().await;
+        let store = data
+            .get::<ModmailKey>()
+            .expect("Expected Modmail in TypeMap.");
+        match store.get(channel) {
+            Some(thread) => (thread.clone(), store.transcripts.clone()),
+            None => return Ok(None),
+        }
+    };
+    let transcript = Transcript {
+        channel,
+        closed_by: closer,
+        closed_at: scheduler::now(),
+        reason,
+        thread: &thread,
+    };
+    storage::save(
+        &transcripts.join(format!("{}.json", channel.0)),
+        &transcript,
+    )
+    .await?;
+    // Only forget the thread once its transcript is safe.
+    ctx.data
+        .write()
+        .await
+        .get_mut::<ModmailKey>()
+        .expect("Expected Modmail in TypeMap.")
+        .threads
+        .remove(&channel.0);
+    save(ctx).await;
+    let name = moderation::guild_name(ctx, thread.guild).await;
+    let text = match reason {
+        Some(r) => format!(
+            "Your modmail thread with **{}** was closed: {}\nDM me again if you need anything else",
+            name, r
+        ),
+        None => format!(
+            "Your modmail thread with **{}** was closed\nDM me again if you need anything else",
+            name
+        ),
+    };
+    moderation::notify(ctx, thread.user, text).await;
+    if let Err(why) = channel.delete(&ctx.http).await {
+        error!("Failed to delete modmail channel {}: {:?}", channel, why);
+    }
+    Ok(Some(thread))
+}
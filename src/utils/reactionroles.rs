@@ -0,0 +1,113 @@
+use crate::utils::storage;
+use serde::{Deserialize, Serialize};
+use serenity::model::channel::ReactionType;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+pub struct ReactionRoleKey;
+
+impl TypeMapKey for ReactionRoleKey {
+    type Value = ReactionRoleStore;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Binding {
+    pub emoji: ReactionType,
+    pub role: RoleId,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReactionMenu {
+    pub guild: GuildId,
+    pub channel: ChannelId,
+    pub bindings: Vec<Binding>,
+}
+
+/// Custom emojis are matched on id alone since their names can change.
+fn same_emoji(a: &ReactionType, b: &ReactionType) -> bool {
+    match (a, b) {
+        (ReactionType::Custom { id: x, .. }, ReactionType::Custom { id: y, .. }) => x == y,
+        (ReactionType::Unicode(x), ReactionType::Unicode(y)) => x == y,
+        _ => false,
+    }
+}
+
+pub struct ReactionRoleStore {
+    path: PathBuf,
+    menus: HashMap<u64, ReactionMenu>,
+}
+
+impl ReactionRoleStore {
+    pub async fn load(path: PathBuf) -> io::Result<ReactionRoleStore> {
+        let menus = storage::load(&path).await?;
+        Ok(ReactionRoleStore { path, menus })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.menus).await
+    }
+
+    pub fn is_menu(&self, message: MessageId) -> bool {
+        self.menus.contains_key(&message.0)
+    }
+
+    pub fn role_for(&self, message: MessageId, emoji: &ReactionType) -> Option<RoleId> {
+        self.menus.get(&message.0).and_then(|m| {
+            m.bindings
+                .iter()
+                .find(|b| same_emoji(&b.emoji, emoji))
+                .map(|b| b.role)
+        })
+    }
+
+    /// Binds `emoji` to `role` on a message, replacing any earlier binding for
+    /// the same emoji.
+    pub fn bind(
+        &mut self,
+        guild: GuildId,
+        channel: ChannelId,
+        message: MessageId,
+        emoji: ReactionType,
+        role: RoleId,
+    ) {
+        let menu = self.menus.entry(message.0).or_insert_with(|| ReactionMenu {
+            guild,
+            channel,
+            bindings: Vec::new(),
+        });
+        menu.bindings.retain(|b| !same_emoji(&b.emoji, &emoji));
+        menu.bindings.push(Binding { emoji, role });
+    }
+
+    /// Removes a binding, dropping the menu once it has none left. Returns
+    /// whether anything was removed.
+    pub fn unbind(&mut self, message: MessageId, emoji: &ReactionType) -> bool {
+        let removed = match self.menus.get_mut(&message.0) {
+            Some(menu) => {
+                let before = menu.bindings.len();
+                menu.bindings.retain(|b| !same_emoji(&b.emoji, emoji));
+                before != menu.bindings.len()
+            }
+            None => false,
+        };
+        if self
+            .menus
+            .get(&message.0)
+            .map_or(false, |m| m.bindings.is_empty())
+        {
+            self.menus.remove(&message.0);
+        }
+        removed
+    }
+
+    pub fn menus_in(&self, guild: GuildId) -> Vec<(MessageId, &ReactionMenu)> {
+        self.menus
+            .iter()
+            .filter(|(_, m)| m.guild == guild)
+            .map(|(id, m)| (MessageId(*id), m))
+            .collect()
+    }
+}
@@ -0,0 +1,148 @@
+use crate::utils::storage;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{GuildId, RoleId};
+use serenity::model::interactions::{
+    message_component::MessageComponentInteraction, InteractionApplicationCommandCallbackDataFlags,
+    InteractionResponseType,
+};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+/// Custom ids look like `rolemenu:<menu>` for select menus and
+/// `rolemenu:<menu>:<role>` for buttons.
+pub const ID_PREFIX: &str = "rolemenu:";
+
+pub struct RoleMenuKey;
+
+impl TypeMapKey for RoleMenuKey {
+    type Value = RoleMenuStore;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RoleMenu {
+    pub guild: GuildId,
+    pub roles: Vec<RoleId>,
+}
+
+pub struct RoleMenuStore {
+    path: PathBuf,
+    menus: HashMap<u64, RoleMenu>,
+}
+
+impl RoleMenuStore {
+    pub async fn load(path: PathBuf) -> io::Result<RoleMenuStore> {
+        let menus = storage::load(&path).await?;
+        Ok(RoleMenuStore { path, menus })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.menus).await
+    }
+
+    pub fn insert(&mut self, id: u64, menu: RoleMenu) {
+        self.menus.insert(id, menu);
+    }
+
+    pub fn get(&self, id: u64) -> Option<&RoleMenu> {
+        self.menus.get(&id)
+    }
+}
+
+async fn respond(ctx: &Context, component: &MessageComponentInteraction, text: String) {
+    let _ = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
+}
+
+/// Toggles the clicked button's role, or makes the member's roles match the
+/// options chosen in a select menu.
+pub async fn handle_component(ctx: &Context, component: &MessageComponentInteraction) {
+    let mut parts = component.data.custom_id[ID_PREFIX.len()..].split(':');
+    let menu_id = parts.next().and_then(|p| p.parse::<u64>().ok());
+    let button_role = parts.next().and_then(|p| p.parse::<u64>().ok()).map(RoleId);
+
+    let menu = {
+        let data = ctx.data.read().await;
+        data.get::<RoleMenuKey>()
+            .expect("Expected RoleMenus in TypeMap.")
+            .get(menu_id.unwrap_or_default())
+            .cloned()
+    };
+    let (menu, mut member) = match (menu, component.member.clone()) {
+        (Some(m), Some(mem)) => (m, mem),
+        _ => {
+            respond(
+                ctx,
+                component,
+                "This role menu no longer exists".to_string(),
+            )
+            .await;
+            return;
+        }
+    };
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut failed = false;
+    match button_role {
+        Some(role) if menu.roles.contains(&role) => {
+            if member.roles.contains(&role) {
+                failed |= member.remove_role(&ctx.http, role).await.is_err();
+                removed.push(role);
+            } else {
+                failed |= member.add_role(&ctx.http, role).await.is_err();
+                added.push(role);
+            }
+        }
+        Some(_) => {}
+        None => {
+            let chosen = component
+                .data
+                .values
+                .iter()
+                .filter_map(|v| v.parse::<u64>().ok().map(RoleId))
+                .collect::<Vec<RoleId>>();
+            for role in &menu.roles {
+                let has = member.roles.contains(role);
+                if chosen.contains(role) && !has {
+                    failed |= member.add_role(&ctx.http, *role).await.is_err();
+                    added.push(*role);
+                } else if !chosen.contains(role) && has {
+                    failed |= member.remove_role(&ctx.http, *role).await.is_err();
+                    removed.push(*role);
+                }
+            }
+        }
+    }
+
+    let mention = |roles: &[RoleId]| {
+        roles
+            .iter()
+            .map(|r| format!("<@&{}>", r.0))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let text = if failed {
+        "Couldn't update all of your roles. Ask a mod to check my permissions".to_string()
+    } else if added.is_empty() && removed.is_empty() {
+        "Your roles are already up to date".to_string()
+    } else {
+        let mut lines = Vec::new();
+        if !added.is_empty() {
+            lines.push(format!("Gave you {}", mention(&added)));
+        }
+        if !removed.is_empty() {
+            lines.push(format!("Removed {}", mention(&removed)));
+        }
+        lines.join("\n")
+    };
+    respond(ctx, component, text).await;
+}
@@ -0,0 +1,312 @@
+use crate::utils::{
+    cases::{self, Case, CaseAction},
+    storage,
+};
+use serde::{Deserialize, Serialize};
+use serenity::http::{Http, HttpError};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::prelude::*;
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+
+/// How often the scheduler looks for expired actions.
+pub const TICK: Duration = Duration::from_secs(10);
+const DISCORD_EPOCH: i64 = 1_420_070_400;
+/// A failed action is retried after a minute, doubling each time, and given
+/// up after this many attempts.
+const MAX_ATTEMPTS: u8 = 5;
+const RETRY_DELAY: i64 = 60;
+
+pub struct SchedulerKey;
+
+impl TypeMapKey for SchedulerKey {
+    type Value = Schedule;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Action {
+    /// Take `role` (the mute role when the mute started) off the member.
+    Unmute { role: RoleId },
+    /// Lift a temporary ban and post a notice in `channel`.
+    Unban { channel: ChannelId },
+    /// Kick the member unless they have `verified` (the user role when they
+    /// joined) by then.
+    KickUnverified { verified: RoleId },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scheduled {
+    pub guild: GuildId,
+    pub user: UserId,
+    pub action: Action,
+    /// Unix timestamp in seconds.
+    pub expires_at: i64,
+    /// Failed attempts so far.
+    #[serde(default)]
+    pub attempts: u8,
+}
+
+pub struct Schedule {
+    path: PathBuf,
+    entries: Vec<Scheduled>,
+}
+
+impl Schedule {
+    pub async fn load(path: PathBuf) -> io::Result<Schedule> {
+        let entries = storage::load(&path).await?;
+        Ok(Schedule { path, entries })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.entries).await
+    }
+
+    /// Adds an entry, replacing any pending entry of the same kind for the
+    /// same member.
+    pub fn add(&mut self, entry: Scheduled) {
+        let kind = mem::discriminant(&entry.action);
+        self.entries.retain(|e| {
+            !(e.guild == entry.guild
+                && e.user == entry.user
+                && mem::discriminant(&e.action) == kind)
+        });
+        self.entries.push(entry);
+    }
+
+    fn take(
+        &mut self,
+        guild: GuildId,
+        user: UserId,
+        is_kind: fn(&Action) -> bool,
+    ) -> Option<Scheduled> {
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| e.guild == guild && e.user == user && is_kind(&e.action))?;
+        Some(self.entries.remove(pos))
+    }
+
+    pub fn take_mute(&mut self, guild: GuildId, user: UserId) -> Option<Scheduled> {
+        self.take(guild, user, |a| matches!(a, Action::Unmute { .. }))
+    }
+
+    pub fn take_ban(&mut self, guild: GuildId, user: UserId) -> Option<Scheduled> {
+        self.take(guild, user, |a| matches!(a, Action::Unban { .. }))
+    }
+
+    pub fn take_kick(&mut self, guild: GuildId, user: UserId) -> Option<Scheduled> {
+        self.take(guild, user, |a| matches!(a, Action::KickUnverified { .. }))
+    }
+
+    pub fn mutes_in(&self, guild: GuildId) -> Vec<&Scheduled> {
+        let mut mutes = self
+            .entries
+            .iter()
+            .filter(|e| e.guild == guild && matches!(e.action, Action::Unmute { .. }))
+            .collect::<Vec<&Scheduled>>();
+        mutes.sort_by_key(|e| e.expires_at);
+        mutes
+    }
+
+    fn due(&self, now: i64) -> Vec<Scheduled> {
+        self.entries
+            .iter()
+            .filter(|e| e.expires_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Where `entry` is, unless it was replaced or cancelled in the meantime.
+    fn position(&self, entry: &Scheduled) -> Option<usize> {
+        self.entries.iter().position(|e| {
+            e.guild == entry.guild
+                && e.user == entry.user
+                && e.expires_at == entry.expires_at
+                && mem::discriminant(&e.action) == mem::discriminant(&entry.action)
+        })
+    }
+
+    fn finish(&mut self, entry: &Scheduled) {
+        if let Some(pos) = self.position(entry) {
+            self.entries.remove(pos);
+        }
+    }
+
+    /// Pushes `entry` back with a growing delay. Returns false once it has
+    /// failed too often and was dropped.
+    fn retry(&mut self, entry: &Scheduled) -> bool {
+        let pos = match self.position(entry) {
+            Some(p) => p,
+            None => return true,
+        };
+        let attempts = entry.attempts + 1;
+        if attempts >= MAX_ATTEMPTS {
+            self.entries.remove(pos);
+            return false;
+        }
+        let retry = &mut self.entries[pos];
+        retry.attempts = attempts;
+        retry.expires_at = now() + (RETRY_DELAY << (attempts - 1));
+        true
+    }
+}
+
+pub fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}
+
+/// When a Discord id (account, message, audit log entry) was created, as a
+/// unix timestamp.
+pub fn created_at(id: u64) -> i64 {
+    (id >> 22) as i64 / 1000 + DISCORD_EPOCH
+}
+
+fn status(why: &SerenityError) -> Option<u16> {
+    match why {
+        SerenityError::Http(http) => match &**http {
+            HttpError::UnsuccessfulRequest(response) => Some(response.status_code.as_u16()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Looks up the one ban rather than downloading the whole ban list.
+pub async fn is_banned(http: &Http, guild: GuildId, user: UserId) -> serenity::Result<bool> {
+    match http.get_ban(guild.0, user.0).await {
+        Ok(_) => Ok(true),
+        // Unknown Ban.
+        Err(why) if status(&why) == Some(404) => Ok(false),
+        Err(why) => Err(why),
+    }
+}
+
+/// Unbans `user` if they are currently banned. Returns whether a ban was
+/// lifted.
+pub async fn lift_ban(http: &Http, guild: GuildId, user: UserId) -> serenity::Result<bool> {
+    let banned = is_banned(http, guild, user).await?;
+    if banned {
+        guild.unban(http, user).await?;
+    }
+    Ok(banned)
+}
+
+/// Returns whether anything was done. An unban or a kick for verification
+/// can turn out to be unnecessary.
+async fn run(http: &Http, entry: &Scheduled) -> serenity::Result<bool> {
+    match &entry.action {
+        Action::Unmute { role } => {
+            http.remove_member_role(entry.guild.0, entry.user.0, role.0)
+                .await?;
+            Ok(true)
+        }
+        Action::Unban { channel } => {
+            // Someone already unbanned them.
+            if !lift_ban(http, entry.guild, entry.user).await? {
+                return Ok(false);
+            }
+            // The unban happened, so a lost notice shouldn't cause a retry.
+            if let Err(why) = channel
+                .say(
+                    http,
+                    format!("Temporary ban for <@{}> expired, unbanned", entry.user.0),
+                )
+                .await
+            {
+                error!(
+                    "Failed to post the tempban notice in {}: {:?}",
+                    channel, why
+                );
+            }
+            Ok(true)
+        }
+        Action::KickUnverified { verified } => {
+            let member = match http.get_member(entry.guild.0, entry.user.0).await {
+                Ok(m) => m,
+                // They already left.
+                Err(_) => return Ok(false),
+            };
+            if member.roles.contains(verified) {
+                return Ok(false);
+            }
+            entry
+                .guild
+                .kick_with_reason(http, entry.user, "Didn't verify in time")
+                .await?;
+            Ok(true)
+        }
+    }
+}
+
+/// Whether trying again later could help: Discord failing or rate limiting,
+/// or the request not getting through. Refusals like Unknown Member or
+/// Missing Permissions won't change.
+fn is_transient(why: &SerenityError) -> bool {
+    match status(why) {
+        Some(code) => code == 429 || code >= 500,
+        None => true,
+    }
+}
+
+/// Carries out every expired action, including ones that came due while the
+/// bot was offline. An entry stays scheduled until its action succeeds or
+/// fails for good; transient failures are retried a few times. Each completed
+/// action is recorded as a case opened by `bot`.
+pub async fn process_due(http: &Http, data: &RwLock<TypeMap>, bot: UserId) {
+    let due = {
+        let data = data.read().await;
+        match data.get::<SchedulerKey>() {
+            Some(s) => s.due(now()),
+            None => return,
+        }
+    };
+    for entry in due {
+        let outcome = run(http, &entry).await;
+        {
+            let mut data = data.write().await;
+            let schedule = data
+                .get_mut::<SchedulerKey>()
+                .expect("Expected Scheduler in TypeMap.");
+            match &outcome {
+                Err(why) if is_transient(why) => {
+                    if schedule.retry(&entry) {
+                        warn!("Scheduled {:?} failed, retrying: {:?}", entry, why);
+                    } else {
+                        error!("Giving up on scheduled {:?}: {:?}", entry, why);
+                    }
+                }
+                Err(why) => {
+                    error!("Scheduled {:?} failed: {:?}", entry, why);
+                    schedule.finish(&entry);
+                }
+                Ok(_) => schedule.finish(&entry),
+            }
+            if let Err(why) = schedule.save().await {
+                error!("Failed to save schedule: {}", why);
+            }
+        }
+        if let Ok(true) = outcome {
+            info!("Ran scheduled {:?}", entry);
+            let (action, reason) = match entry.action {
+                Action::Unmute { .. } => (CaseAction::Unmute, "Mute expired"),
+                Action::Unban { .. } => (CaseAction::Unban, "Temporary ban expired"),
+                Action::KickUnverified { .. } => (CaseAction::Kick, "Didn't verify in time"),
+            };
+            let case = Case::new(action, entry.user, bot, Some(reason.to_string()));
+            cases::open(http, data, entry.guild, case).await;
+        }
+    }
+}
+
+pub async fn run_forever(http: Arc<Http>, data: Arc<RwLock<TypeMap>>, bot: UserId) {
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        process_due(&http, &data, bot).await;
+    }
+}
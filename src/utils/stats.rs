@@ -0,0 +1,208 @@
+use crate::utils::storage;
+use crate::{CommandCounter, EventCounter};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use time::OffsetDateTime;
+use tracing::error;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+/// Hourly buckets are only needed for the `24h` and `7d` windows.
+const HOURLY_RETENTION: i64 = 7 * DAY;
+pub const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Window {
+    Day,
+    Week,
+    All,
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "24h" | "1d" | "day" => Ok(Window::Day),
+            "7d" | "1w" | "week" => Ok(Window::Week),
+            "all" => Ok(Window::All),
+            _ => Err(format!(
+                "`{}` is not a time window. Use `24h`, `7d` or `all`",
+                s
+            )),
+        }
+    }
+}
+
+impl Window {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Window::Day => "last 24 hours",
+            Window::Week => "last 7 days",
+            Window::All => "all time",
+        }
+    }
+
+    fn rate_unit(&self) -> (&'static str, i64) {
+        match self {
+            Window::Day => ("h", HOUR),
+            _ => ("day", DAY),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Series {
+    hourly: BTreeMap<i64, u64>,
+    daily: BTreeMap<i64, u64>,
+}
+
+impl Series {
+    fn record(&mut self, now: i64) {
+        *self.hourly.entry(now - now.rem_euclid(HOUR)).or_insert(0) += 1;
+        *self.daily.entry(now - now.rem_euclid(DAY)).or_insert(0) += 1;
+    }
+
+    fn prune(&mut self, now: i64) {
+        let cutoff = now - HOURLY_RETENTION;
+        self.hourly = self.hourly.split_off(&cutoff);
+    }
+
+    fn total(&self, window: Window, now: i64) -> u64 {
+        match window {
+            Window::Day => self.hourly.range(now - DAY..).map(|(_, v)| v).sum(),
+            Window::Week => self.hourly.range(now - 7 * DAY..).map(|(_, v)| v).sum(),
+            Window::All => self.daily.values().sum(),
+        }
+    }
+
+    fn first_seen(&self) -> Option<i64> {
+        self.daily.keys().next().copied()
+    }
+}
+
+pub struct Tally {
+    pub name: String,
+    pub total: u64,
+    pub rate: String,
+}
+
+/// Totals for every series over `window`, busiest first. Rates are per hour
+/// for `24h` and per day otherwise; `all` is measured from the first record.
+fn tally<'a>(series: impl Iterator<Item = (&'a String, &'a Series)>, window: Window) -> Vec<Tally> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let series: Vec<(&String, &Series)> = series.collect();
+    let span = match window {
+        Window::Day => DAY,
+        Window::Week => 7 * DAY,
+        Window::All => series
+            .iter()
+            .filter_map(|(_, s)| s.first_seen())
+            .min()
+            .map(|first| now - first)
+            .unwrap_or(DAY),
+    };
+    let (unit, unit_secs) = window.rate_unit();
+    let units = (span as f64 / unit_secs as f64).max(1.0);
+    let mut tallies = series
+        .into_iter()
+        .map(|(name, s)| {
+            let total = s.total(window, now);
+            Tally {
+                name: name.clone(),
+                total,
+                rate: format!("{:.2}/{}", total as f64 / units, unit),
+            }
+        })
+        .filter(|t| t.total > 0)
+        .collect::<Vec<Tally>>();
+    tallies.sort_by(|a, b| b.total.cmp(&a.total));
+    tallies
+}
+
+/// Command uses per guild. Direct messages are filed under guild `0`.
+pub struct CommandStats {
+    path: PathBuf,
+    guilds: HashMap<u64, HashMap<String, Series>>,
+}
+
+impl CommandStats {
+    pub async fn load(path: PathBuf) -> io::Result<CommandStats> {
+        let guilds = storage::load(&path).await?;
+        Ok(CommandStats { path, guilds })
+    }
+
+    pub async fn save(&mut self) -> io::Result<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.guilds
+            .values_mut()
+            .flat_map(|g| g.values_mut())
+            .for_each(|s| s.prune(now));
+        storage::save(&self.path, &self.guilds).await
+    }
+
+    pub fn record(&mut self, guild: Option<GuildId>, command: &str) {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.guilds
+            .entry(guild.map(|g| g.0).unwrap_or(0))
+            .or_default()
+            .entry(command.to_string())
+            .or_default()
+            .record(now);
+    }
+
+    pub fn tally(&self, guild: Option<GuildId>, window: Window) -> Vec<Tally> {
+        match self.guilds.get(&guild.map(|g| g.0).unwrap_or(0)) {
+            Some(commands) => tally(commands.iter(), window),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Gateway events by event type.
+pub struct EventStats {
+    path: PathBuf,
+    events: HashMap<String, Series>,
+}
+
+impl EventStats {
+    pub async fn load(path: PathBuf) -> io::Result<EventStats> {
+        let events = storage::load(&path).await?;
+        Ok(EventStats { path, events })
+    }
+
+    pub async fn save(&mut self) -> io::Result<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.events.values_mut().for_each(|s| s.prune(now));
+        storage::save(&self.path, &self.events).await
+    }
+
+    pub fn record(&mut self, event: String) {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.events.entry(event).or_default().record(now);
+    }
+
+    pub fn tally(&self, window: Window) -> Vec<Tally> {
+        tally(self.events.iter(), window)
+    }
+}
+
+/// Writes both counters to disk. Called periodically and on shutdown.
+pub async fn flush(data: &RwLock<TypeMap>) {
+    let mut data = data.write().await;
+    if let Some(commands) = data.get_mut::<CommandCounter>() {
+        if let Err(why) = commands.save().await {
+            error!("Failed to save command stats: {}", why);
+        }
+    }
+    if let Some(events) = data.get_mut::<EventCounter>() {
+        if let Err(why) = events.save().await {
+            error!("Failed to save event stats: {}", why);
+        }
+    }
+}
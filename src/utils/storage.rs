@@ -0,0 +1,30 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::io;
+use std::path::Path;
+use tokio::fs;
+
+/// Reads a JSON file into `T`. A missing file is treated as an empty store.
+pub async fn load<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    match fs::read(path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not valid: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes `value` as JSON. The file is written next to the target and renamed
+/// over it so a crash mid-write never leaves a truncated store behind.
+pub async fn save<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let bytes = serde_json::to_vec_pretty(value)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, bytes).await?;
+    fs::rename(&tmp, path).await
+}
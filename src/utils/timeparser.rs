@@ -1,5 +1,8 @@
 #[allow(unused_imports)]
 use num_integer::Integer;
+use serenity::framework::standard::{ArgError, Args};
+use std::fmt;
+use std::str::FromStr;
 use time::Duration;
 
 pub fn humanise_time(interval: Duration) -> String {
@@ -29,3 +32,283 @@ pub fn min_sec_parse(interval: Duration) -> String {
         interval.whole_seconds() - (interval.whole_minutes() * 60)
     );
 }
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+/// Nothing the bot schedules needs to run further out than this.
+const MAX_SECONDS: i64 = 5 * 365 * DAY;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DurationError {
+    Empty,
+    Invalid(String),
+    MissingUnit(String),
+    UnknownUnit(String),
+    NotPositive,
+    TooLong,
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationError::Empty => write!(
+                f,
+                "No duration given. Try something like `90s`, `1h30m` or `2d`"
+            ),
+            DurationError::Invalid(s) => write!(
+                f,
+                "`{}` is not a duration. Try something like `90s`, `1h30m` or `2d`",
+                s
+            ),
+            DurationError::MissingUnit(n) => {
+                write!(f, "`{}` needs a unit, like `{}m` or `{}h`", n, n, n)
+            }
+            DurationError::UnknownUnit(u) => write!(
+                f,
+                "`{}` is not a time unit. Use `s`, `m`, `h`, `d` or `w`",
+                u
+            ),
+            DurationError::NotPositive => write!(f, "The duration must be longer than zero"),
+            DurationError::TooLong => write!(f, "That duration is too long. The limit is 5 years"),
+        }
+    }
+}
+
+impl std::error::Error for DurationError {}
+
+fn unit_seconds(unit: &str) -> Option<i64> {
+    match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(MINUTE),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(HOUR),
+        "d" | "day" | "days" => Some(DAY),
+        "w" | "wk" | "wks" | "week" | "weeks" => Some(WEEK),
+        _ => None,
+    }
+}
+
+/// A duration typed by a user, such as `90s`, `1h30m`, `2d`, `1w 2d` or
+/// `tomorrow` (24 hours from now). A bare number is read as seconds.
+///
+/// It implements `FromStr`, so `args.single::<HumanDuration>()` works for a
+/// single token. Use [`HumanDuration::from_args`] to also accept durations
+/// split over several arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn whole_seconds(&self) -> i64 {
+        self.0.whole_seconds()
+    }
+
+    /// Reads a duration from the next argument, then keeps consuming
+    /// arguments while they are unit-suffixed parts like `2d` or `30m`.
+    pub fn from_args(args: &mut Args) -> Result<HumanDuration, DurationError> {
+        let mut total = match args.single::<HumanDuration>() {
+            Ok(d) => d.whole_seconds(),
+            Err(ArgError::Parse(why)) => return Err(why),
+            Err(_) => return Err(DurationError::Empty),
+        };
+        loop {
+            let next = match args.current() {
+                Some(c)
+                    if c.starts_with(|ch: char| ch.is_ascii_digit())
+                        && c.parse::<i64>().is_err() =>
+                {
+                    c
+                }
+                _ => break,
+            };
+            match parse_seconds(next) {
+                Ok(secs) => {
+                    total = total
+                        .checked_add(secs)
+                        .filter(|t| *t <= MAX_SECONDS)
+                        .ok_or(DurationError::TooLong)?;
+                    args.advance();
+                }
+                Err(_) => break,
+            }
+        }
+        Ok(HumanDuration(Duration::seconds(total)))
+    }
+}
+
+fn parse_seconds(input: &str) -> Result<i64, DurationError> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return Err(DurationError::Empty);
+    }
+    if input == "tomorrow" {
+        return Ok(DAY);
+    }
+
+    let mut total: i64 = 0;
+    let mut chars = input.chars().peekable();
+    loop {
+        while chars
+            .peek()
+            .map_or(false, |c| c.is_whitespace() || *c == ',')
+        {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut number = String::new();
+        while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            number.push(*c);
+            chars.next();
+        }
+        if number.is_empty() {
+            return Err(DurationError::Invalid(input.clone()));
+        }
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut unit = String::new();
+        while let Some(c) = chars.peek().filter(|c| c.is_alphabetic()) {
+            unit.push(*c);
+            chars.next();
+        }
+        let multiplier = if unit.is_empty() {
+            // A lone number is seconds, but `1h 30` is ambiguous.
+            if total == 0 && chars.peek().is_none() {
+                1
+            } else {
+                return Err(DurationError::MissingUnit(number));
+            }
+        } else {
+            unit_seconds(&unit).ok_or(DurationError::UnknownUnit(unit))?
+        };
+        total = number
+            .parse::<i64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or(DurationError::TooLong)?;
+    }
+
+    if total <= 0 {
+        Err(DurationError::NotPositive)
+    } else if total > MAX_SECONDS {
+        Err(DurationError::TooLong)
+    } else {
+        Ok(total)
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = DurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_seconds(s).map(|secs| HumanDuration(Duration::seconds(secs)))
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    /// Compact form such as `1w 2d 3h`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.whole_seconds();
+        let mut parts = Vec::new();
+        for (unit, secs) in [
+            ("w", WEEK),
+            ("d", DAY),
+            ("h", HOUR),
+            ("m", MINUTE),
+            ("s", 1),
+        ] {
+            if rest >= secs {
+                parts.push(format!("{}{}", rest / secs, unit));
+                rest %= secs;
+            }
+        }
+        if parts.is_empty() {
+            parts.push("0s".to_string());
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::framework::standard::Delimiter;
+
+    fn seconds(input: &str) -> Result<i64, DurationError> {
+        input.parse::<HumanDuration>().map(|d| d.whole_seconds())
+    }
+
+    #[test]
+    fn parses_valid_durations() {
+        assert_eq!(seconds("90s"), Ok(90));
+        assert_eq!(seconds("90"), Ok(90));
+        assert_eq!(seconds("1h30m"), Ok(HOUR + 30 * MINUTE));
+        assert_eq!(seconds("1w 2d"), Ok(WEEK + 2 * DAY));
+        assert_eq!(seconds("2 days, 3 hours"), Ok(2 * DAY + 3 * HOUR));
+        assert_eq!(seconds("1H30M"), Ok(HOUR + 30 * MINUTE));
+        assert_eq!(seconds("tomorrow"), Ok(DAY));
+    }
+
+    #[test]
+    fn rejects_zero() {
+        assert_eq!(seconds("0s"), Err(DurationError::NotPositive));
+        assert_eq!(seconds("0"), Err(DurationError::NotPositive));
+        assert_eq!(seconds("0h 0m"), Err(DurationError::NotPositive));
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(seconds("300w"), Err(DurationError::TooLong));
+        assert_eq!(seconds("9223372036854775807w"), Err(DurationError::TooLong));
+        assert_eq!(
+            seconds("99999999999999999999s"),
+            Err(DurationError::TooLong)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_eq!(seconds(""), Err(DurationError::Empty));
+        assert_eq!(seconds("   "), Err(DurationError::Empty));
+        assert_eq!(
+            seconds("abc"),
+            Err(DurationError::Invalid("abc".to_string()))
+        );
+        assert_eq!(
+            seconds("-5m"),
+            Err(DurationError::Invalid("-5m".to_string()))
+        );
+        assert_eq!(
+            seconds("1h 30"),
+            Err(DurationError::MissingUnit("30".to_string()))
+        );
+        assert_eq!(
+            seconds("6y"),
+            Err(DurationError::UnknownUnit("y".to_string()))
+        );
+        assert_eq!(
+            seconds("5x"),
+            Err(DurationError::UnknownUnit("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_durations_split_over_arguments() {
+        let mut args = Args::new("1h 30m being rude", &[Delimiter::Single(' ')]);
+        let duration = HumanDuration::from_args(&mut args).unwrap();
+        assert_eq!(duration.whole_seconds(), HOUR + 30 * MINUTE);
+        assert_eq!(args.rest(), "being rude");
+    }
+
+    #[test]
+    fn displays_compactly() {
+        assert_eq!(
+            HumanDuration(Duration::seconds(WEEK + 2 * DAY + 3 * HOUR)).to_string(),
+            "1w 2d 3h"
+        );
+        assert_eq!(HumanDuration(Duration::seconds(0)).to_string(), "0s");
+    }
+}
@@ -0,0 +1,330 @@
+use crate::utils::{
+    guildconfig,
+    moderation::{self, guild_name},
+    scheduler::{self, Action, Scheduled, SchedulerKey},
+    storage,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serenity::model::channel::Message;
+use serenity::model::guild::Member;
+use serenity::model::id::{GuildId, UserId};
+use serenity::model::interactions::{
+    message_component::MessageComponentInteraction, InteractionApplicationCommandCallbackDataFlags,
+    InteractionResponseType,
+};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use tracing::error;
+
+/// The rules button's custom id. It carries no state, so buttons posted
+/// before a restart keep working.
+pub const ID_PREFIX: &str = "verify:";
+
+/// Unanswered questions expire after this many seconds.
+const CHALLENGE_TIME: i64 = 5 * 60;
+const MAX_ATTEMPTS: u8 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct VerifySettings {
+    /// Ask an arithmetic question by DM before verifying.
+    pub challenge: bool,
+    /// Kick members who haven't verified this many seconds after joining.
+    pub kick_after: Option<i64>,
+}
+
+/// Questions waiting for an answer, keyed by guild and user so members can
+/// verify in several servers at once. Not persisted; members can ask for a
+/// new question by running `verify` again.
+pub struct ChallengeKey;
+
+impl TypeMapKey for ChallengeKey {
+    type Value = Challenges;
+}
+
+struct Challenge {
+    answer: i64,
+    attempts: u8,
+    expires_at: i64,
+}
+
+#[derive(Default)]
+pub struct Challenges {
+    pending: HashMap<(u64, u64), Challenge>,
+}
+
+/// When each member verified, for the mod team.
+pub struct VerifiedKey;
+
+impl TypeMapKey for VerifiedKey {
+    type Value = VerifiedStore;
+}
+
+pub struct VerifiedStore {
+    path: PathBuf,
+    guilds: HashMap<u64, HashMap<u64, i64>>,
+}
+
+impl VerifiedStore {
+    pub async fn load(path: PathBuf) -> io::Result<VerifiedStore> {
+        let guilds = storage::load(&path).await?;
+        Ok(VerifiedStore { path, guilds })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.guilds).await
+    }
+
+    pub fn get(&self, guild: GuildId, user: UserId) -> Option<i64> {
+        self.guilds.get(&guild.0)?.get(&user.0).copied()
+    }
+
+    /// The most recent verifications in `guild`, newest first.
+    pub fn recent(&self, guild: GuildId, count: usize) -> Vec<(UserId, i64)> {
+        let mut verified = match self.guilds.get(&guild.0) {
+            Some(users) => users
+                .iter()
+                .map(|(user, at)| (UserId(*user), *at))
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        verified.sort_by_key(|(_, at)| -at);
+        verified.truncate(count);
+        verified
+    }
+
+    fn record(&mut self, guild: GuildId, user: UserId, at: i64) {
+        self.guilds.entry(guild.0).or_default().insert(user.0, at);
+    }
+}
+
+fn question() -> (String, i64) {
+    let mut rng = rand::thread_rng();
+    let a = rng.gen_range(2..=20);
+    let b = rng.gen_range(2..=20);
+    match rng.gen_range(0..3) {
+        0 => (format!("{} + {}", a, b), a + b),
+        1 => (format!("{} - {}", a.max(b), a.min(b)), a.max(b) - a.min(b)),
+        _ => (
+            format!("{} × {}", a % 10 + 1, b % 10 + 1),
+            (a % 10 + 1) * (b % 10 + 1),
+        ),
+    }
+}
+
+/// Gives the member the user role and takes the unverified role. Returns
+/// false if they were already verified or no user role is configured.
+pub async fn complete(ctx: &Context, guild: GuildId, user: UserId) -> serenity::Result<bool> {
+    let settings = guildconfig::settings(ctx, guild).await;
+    let verified = match settings.user_role {
+        Some(r) => r,
+        None => return Ok(false),
+    };
+    let mut member = guild.member(ctx, user).await?;
+    if member.roles.contains(&verified) {
+        return Ok(false);
+    }
+    member.add_role(&ctx.http, verified).await?;
+    if let Some(unverified) = settings.unverified_role {
+        if member.roles.contains(&unverified) {
+            member.remove_role(&ctx.http, unverified).await?;
+        }
+    }
+    let mut data = ctx.data.write().await;
+    let log = data
+        .get_mut::<VerifiedKey>()
+        .expect("Expected Verified in TypeMap.");
+    log.record(guild, user, scheduler::now());
+    if let Err(why) = log.save().await {
+        error!("Failed to save verifications: {}", why);
+    }
+    let schedule = data
+        .get_mut::<SchedulerKey>()
+        .expect("Expected Scheduler in TypeMap.");
+    if schedule.take_kick(guild, user).is_some() {
+        if let Err(why) = schedule.save().await {
+            error!("Failed to save schedule: {}", why);
+        }
+    }
+    Ok(true)
+}
+
+/// Verifies the member, or sends them the challenge if the guild uses one.
+/// Shared by `verify` and the rules button. Returns what to tell them.
+pub async fn begin(ctx: &Context, guild: GuildId, user: UserId) -> serenity::Result<&'static str> {
+    let settings = guildconfig::settings(ctx, guild).await;
+    let verified = match settings.user_role {
+        Some(r) => r,
+        None => return Ok("Verification isn't set up here. Ask a mod for help"),
+    };
+    if guild.member(ctx, user).await?.roles.contains(&verified) {
+        return Ok("You're already verified");
+    }
+    if !settings.verification.challenge {
+        complete(ctx, guild, user).await?;
+        Ok("You have been verified")
+    } else if challenge(ctx, guild, user).await {
+        Ok("Check your DMs for a question to answer")
+    } else {
+        Ok("I couldn't DM you. Allow DMs from server members and try again")
+    }
+}
+
+/// Handles a press of the rules button. The reply is only shown to the
+/// member who pressed it.
+pub async fn handle_component(ctx: &Context, component: &MessageComponentInteraction) {
+    let guild = match component.guild_id {
+        Some(g) => g,
+        None => return,
+    };
+    let text = match begin(ctx, guild, component.user.id).await {
+        Ok(text) => text,
+        Err(why) => {
+            error!(
+                "Failed to verify {} in {}: {:?}",
+                component.user.id, guild, why
+            );
+            "Couldn't give you your roles. Ask a mod for help"
+        }
+    };
+    let _ = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
+}
+
+/// DMs the member a question to answer before they are verified. Returns
+/// whether the DM was delivered.
+pub async fn challenge(ctx: &Context, guild: GuildId, user: UserId) -> bool {
+    let (question, answer) = question();
+    let text = format!(
+        "To verify in **{}**, reply with just the number that answers: **{}**\nThis question expires in 5 minutes",
+        guild_name(ctx, guild).await,
+        question
+    );
+    if !moderation::notify(ctx, user, text).await {
+        return false;
+    }
+    let mut data = ctx.data.write().await;
+    data.get_mut::<ChallengeKey>()
+        .expect("Expected Challenges in TypeMap.")
+        .pending
+        .insert(
+            (guild.0, user.0),
+            Challenge {
+                answer,
+                attempts: 0,
+                expires_at: scheduler::now() + CHALLENGE_TIME,
+            },
+        );
+    true
+}
+
+/// Checks a DM against the author's pending questions. Only DMs that are a
+/// number count as answers; anything else is left for modmail. Returns true
+/// if the message was taken as an answer.
+pub async fn check_answer(ctx: &Context, msg: &Message) -> bool {
+    if msg.author.bot || msg.guild_id.is_some() {
+        return false;
+    }
+    let guess = match msg.content.trim().parse::<i64>() {
+        Ok(n) => n,
+        Err(_) => return false,
+    };
+    let user = msg.author.id.0;
+    let (guild, reply) = {
+        let mut data = ctx.data.write().await;
+        let challenges = data
+            .get_mut::<ChallengeKey>()
+            .expect("Expected Challenges in TypeMap.");
+        let now = scheduler::now();
+        let mut expired = false;
+        challenges.pending.retain(|(_, u), c| {
+            let keep = *u != user || c.expires_at >= now;
+            expired |= !keep;
+            keep
+        });
+        let answered = challenges
+            .pending
+            .iter()
+            .find(|((_, u), c)| *u == user && c.answer == guess)
+            .map(|((g, _), _)| *g);
+        let waiting = challenges.pending.keys().any(|(_, u)| *u == user);
+        if let Some(guild) = answered {
+            challenges.pending.remove(&(guild, user));
+            (Some(GuildId(guild)), String::new())
+        } else if !waiting {
+            if !expired {
+                return false;
+            }
+            (
+                None,
+                "That question expired. Run `verify` again for a new one".to_string(),
+            )
+        } else {
+            for ((_, u), challenge) in challenges.pending.iter_mut() {
+                if *u == user {
+                    challenge.attempts += 1;
+                }
+            }
+            let before = challenges.pending.len();
+            challenges
+                .pending
+                .retain(|(_, u), c| *u != user || c.attempts < MAX_ATTEMPTS);
+            if challenges.pending.len() < before {
+                (
+                    None,
+                    "Too many wrong answers. Run `verify` again for a new question".to_string(),
+                )
+            } else {
+                (None, "That's not right, try again".to_string())
+            }
+        }
+    };
+    let reply = match guild {
+        None => reply,
+        Some(guild) => match complete(ctx, guild, msg.author.id).await {
+            Ok(_) => format!(
+                "You have been verified in **{}**",
+                guild_name(ctx, guild).await
+            ),
+            Err(why) => {
+                error!("Failed to verify {} in {}: {:?}", msg.author.id, guild, why);
+                "Couldn't give you your roles. Ask a mod for help".to_string()
+            }
+        },
+    };
+    let _ = msg.channel_id.say(&ctx.http, reply).await;
+    true
+}
+
+/// Schedules the kick for members who don't verify in time.
+pub async fn on_join(ctx: &Context, guild: GuildId, member: &Member) {
+    let settings = guildconfig::settings(ctx, guild).await;
+    let (verified, after) = match (settings.user_role, settings.verification.kick_after) {
+        (Some(r), Some(a)) => (r, a),
+        _ => return,
+    };
+    let mut data = ctx.data.write().await;
+    let schedule = data
+        .get_mut::<SchedulerKey>()
+        .expect("Expected Scheduler in TypeMap.");
+    schedule.add(Scheduled {
+        guild,
+        user: member.user.id,
+        action: Action::KickUnverified { verified },
+        expires_at: scheduler::now() + after,
+        attempts: 0,
+    });
+    if let Err(why) = schedule.save().await {
+        error!("Failed to save schedule: {}", why);
+    }
+}
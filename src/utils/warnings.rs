@@ -0,0 +1,290 @@
+use crate::utils::{
+    cases::{self, Case, CaseAction},
+    guildconfig,
+    moderation::{self, ActionError, Notice},
+    scheduler, storage,
+    timeparser::HumanDuration,
+};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub struct WarningKey;
+
+impl TypeMapKey for WarningKey {
+    type Value = WarningStore;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Warning {
+    /// The case opened for this warning.
+    pub case: u64,
+    pub moderator: UserId,
+    pub reason: String,
+    pub created_at: i64,
+}
+
+pub struct WarningStore {
+    path: PathBuf,
+    /// Guild id → user id → warnings, oldest first.
+    guilds: HashMap<u64, HashMap<u64, Vec<Warning>>>,
+}
+
+impl WarningStore {
+    pub async fn load(path: PathBuf) -> io::Result<WarningStore> {
+        let guilds = storage::load(&path).await?;
+        Ok(WarningStore { path, guilds })
+    }
+
+    pub async fn save(&self) -> io::Result<()> {
+        storage::save(&self.path, &self.guilds).await
+    }
+
+    pub fn add(&mut self, guild: GuildId, user: UserId, warning: Warning) {
+        self.guilds
+            .entry(guild.0)
+            .or_default()
+            .entry(user.0)
+            .or_default()
+            .push(warning);
+    }
+
+    pub fn list(&self, guild: GuildId, user: UserId) -> &[Warning] {
+        self.guilds
+            .get(&guild.0)
+            .and_then(|g| g.get(&user.0))
+            .map(|w| w.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Warnings given since `since` (a unix timestamp).
+    pub fn count_since(&self, guild: GuildId, user: UserId, since: i64) -> usize {
+        self.list(guild, user)
+            .iter()
+            .filter(|w| w.created_at >= since)
+            .count()
+    }
+
+    /// Clears one warning by its case number, or all of them. Returns how many
+    /// were removed.
+    pub fn clear(&mut self, guild: GuildId, user: UserId, case: Option<u64>) -> usize {
+        let warnings = match self
+            .guilds
+            .get_mut(&guild.0)
+            .and_then(|g| g.get_mut(&user.0))
+        {
+            Some(w) => w,
+            None => return 0,
+        };
+        let before = warnings.len();
+        match case {
+            Some(n) => warnings.retain(|w| w.case != n),
+            None => warnings.clear(),
+        }
+        let removed = before - warnings.len();
+        if warnings.is_empty() {
+            if let Some(g) = self.guilds.get_mut(&guild.0) {
+                g.remove(&user.0);
+            }
+        }
+        removed
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Punishment {
+    /// Mute for this many seconds.
+    Mute(i64),
+    Kick,
+    /// Ban for this many seconds.
+    Tempban(i64),
+    Ban,
+}
+
+impl fmt::Display for Punishment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let length = |secs: i64| HumanDuration(time::Duration::seconds(secs));
+        match self {
+            Punishment::Mute(secs) => write!(f, "{} mute", length(*secs)),
+            Punishment::Kick => write!(f, "kick"),
+            Punishment::Tempban(secs) => write!(f, "{} ban", length(*secs)),
+            Punishment::Ban => write!(f, "ban"),
+        }
+    }
+}
+
+/// An escalation rule like "3 warnings in 7 days → 1h mute".
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WarnRule {
+    pub warnings: usize,
+    /// Only count warnings from this many seconds back. `None` counts all.
+    pub within: Option<i64>,
+    pub punishment: Punishment,
+}
+
+impl fmt::Display for WarnRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} warnings", self.warnings)?;
+        if let Some(secs) = self.within {
+            write!(f, " in {}", HumanDuration(time::Duration::seconds(secs)))?;
+        }
+        write!(f, " → {}", self.punishment)
+    }
+}
+
+impl FromStr for Punishment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, char::is_whitespace);
+        let kind = parts.next().unwrap_or_default().to_lowercase();
+        let length = parts
+            .next()
+            .map(|d| d.parse::<HumanDuration>().map_err(|e| e.to_string()))
+            .transpose()?;
+        match (kind.as_str(), length) {
+            ("mute", Some(d)) => Ok(Punishment::Mute(d.whole_seconds())),
+            ("mute", None) => Err("A mute needs a duration, like `mute 1h`".to_string()),
+            ("kick", None) => Ok(Punishment::Kick),
+            ("ban", Some(d)) => Ok(Punishment::Tempban(d.whole_seconds())),
+            ("ban", None) => Ok(Punishment::Ban),
+            _ => Err(format!(
+                "`{}` is not a punishment. Use `mute <duration>`, `kick`, `ban` or `ban <duration>`",
+                s
+            )),
+        }
+    }
+}
+
+/// The rule that fired after a warning and the case it opened, if any.
+pub type Escalation = Result<Option<(WarnRule, Case)>, ActionError>;
+
+/// Records a warning as a case, tells the member by DM and applies the
+/// escalation rules. Returns the warning's case, the member's warning count
+/// and the outcome of the escalation. A failed escalation doesn't undo the
+/// warning, so it is returned alongside it rather than as the error.
+pub async fn warn(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    reason: String,
+    notice: ChannelId,
+) -> Result<(Case, usize, Escalation), ActionError> {
+    let warned = Notice {
+        action: CaseAction::Warn,
+        reason: Some(reason.clone()),
+        duration: None,
+    };
+    moderation::check_and_notify(ctx, guild, moderator, user, &warned, false).await?;
+    let case = Case::new(CaseAction::Warn, user, moderator, Some(reason.clone()));
+    let case = cases::open(&ctx.http, &ctx.data, guild, case).await;
+    let total = {
+        let mut data = ctx.data.write().await;
+        let store = data
+            .get_mut::<WarningKey>()
+            .expect("Expected Warnings in TypeMap.");
+        store.add(
+            guild,
+            user,
+            Warning {
+                case: case.number,
+                moderator,
+                reason: reason.clone(),
+                created_at: case.created_at,
+            },
+        );
+        store.save().await?;
+        store.list(guild, user).len()
+    };
+    let escalation = escalate(ctx, guild, user, moderator, notice).await;
+    Ok((case, total, escalation))
+}
+
+/// The harshest rule whose threshold the latest warning hit exactly. Rules
+/// already passed don't fire again on later warnings.
+fn matching_rule(
+    store: &WarningStore,
+    rules: &[WarnRule],
+    guild: GuildId,
+    user: UserId,
+) -> Option<WarnRule> {
+    let now = scheduler::now();
+    rules
+        .iter()
+        .filter(|r| {
+            let since = r.within.map_or(i64::MIN, |w| now - w);
+            store.count_since(guild, user, since) == r.warnings
+        })
+        .max_by_key(|r| r.warnings)
+        .copied()
+}
+
+/// Applies the guild's escalation rules after a new warning. Returns the rule
+/// that fired and the case it opened, if any. The bot takes the action, but
+/// only if the warning's `moderator` could have taken it themselves. `notice`
+/// is where a tempban's expiry notice goes.
+async fn escalate(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    moderator: UserId,
+    notice: ChannelId,
+) -> Escalation {
+    let rules = guildconfig::settings(ctx, guild).await.warn_rules;
+    let rule = {
+        let data = ctx.data.read().await;
+        let store = data
+            .get::<WarningKey>()
+            .expect("Expected Warnings in TypeMap.");
+        matching_rule(store, &rules, guild, user)
+    };
+    let rule = match rule {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+    let action = match rule.punishment {
+        Punishment::Mute(_) => CaseAction::Mute,
+        Punishment::Kick => CaseAction::Kick,
+        Punishment::Tempban(_) => CaseAction::Tempban,
+        Punishment::Ban => CaseAction::Ban,
+    };
+    moderation::check_target(ctx, guild, moderator, user, action).await?;
+    let bot = ctx.cache.current_user_id().await;
+    let reason = format!("Reached {}", rule);
+    let told = format!(
+        "You have received a {} in {}: {}",
+        rule.punishment,
+        moderation::guild_name(ctx, guild).await,
+        reason
+    );
+    let case = match rule.punishment {
+        // A mute can still fail, so they are only told once it worked.
+        Punishment::Mute(secs) => {
+            let length = HumanDuration(time::Duration::seconds(secs));
+            let case = moderation::mute(ctx, guild, user, bot, Some(reason), length).await?;
+            moderation::notify(ctx, user, told).await;
+            case
+        }
+        // Tell them before a kick or ban, while we still share a server.
+        Punishment::Kick => {
+            moderation::notify(ctx, user, told).await;
+            moderation::kick(ctx, guild, user, bot, reason).await?
+        }
+        Punishment::Tempban(secs) => {
+            moderation::notify(ctx, user, told).await;
+            let length = HumanDuration(time::Duration::seconds(secs));
+            moderation::tempban(ctx, guild, user, bot, reason, length, notice).await?
+        }
+        Punishment::Ban => {
+            moderation::notify(ctx, user, told).await;
+            moderation::ban(ctx, guild, user, bot, reason, 0).await?
+        }
+    };
+    Ok(Some((rule, case)))
+}
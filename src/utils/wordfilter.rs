@@ -0,0 +1,172 @@
+use crate::utils::{guildconfig, modlog};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serenity::model::channel::Message;
+use serenity::model::guild::Member;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::error;
+use unidecode::unidecode;
+
+/// Keeps user supplied patterns from compiling into something huge.
+const PATTERN_SIZE_LIMIT: usize = 1 << 16;
+/// Replaces nicknames that trip the filter.
+const MODERATED_NICKNAME: &str = "Moderated Nickname";
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct FilterSettings {
+    /// Matched as whole words.
+    pub words: Vec<String>,
+    /// Regular expressions, matched case-insensitively.
+    pub patterns: Vec<String>,
+}
+
+/// Compiled filters per guild, rebuilt after the lists change.
+pub struct WordFilterKey;
+
+impl TypeMapKey for WordFilterKey {
+    type Value = FilterCache;
+}
+
+type Compiled = Arc<Vec<(String, Regex)>>;
+
+#[derive(Default)]
+pub struct FilterCache {
+    guilds: HashMap<u64, Compiled>,
+}
+
+impl FilterCache {
+    pub fn invalidate(&mut self, guild: GuildId) {
+        self.guilds.remove(&guild.0);
+    }
+}
+
+/// Folds homoglyphs and accented or zalgo text to plain lowercase ASCII so
+/// `ｂáď` and `b̷a̷d̷` both read as `bad`.
+pub fn normalize(text: &str) -> String {
+    unidecode(text).to_lowercase()
+}
+
+pub fn compile_word(word: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!(
+        "(?:^|[^a-z0-9]){}(?:$|[^a-z0-9])",
+        regex::escape(&normalize(word))
+    ))
+}
+
+pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+}
+
+fn compile(settings: &FilterSettings) -> Compiled {
+    let words = settings
+        .words
+        .iter()
+        .filter_map(|w| compile_word(w).ok().map(|r| (w.clone(), r)));
+    let patterns = settings
+        .patterns
+        .iter()
+        .filter_map(|p| compile_pattern(p).ok().map(|r| (p.clone(), r)));
+    Arc::new(words.chain(patterns).collect())
+}
+
+/// The blocked word or pattern `text` matches, if any.
+pub async fn find_match(ctx: &Context, guild: GuildId, text: &str) -> Option<String> {
+    let cached = {
+        let data = ctx.data.read().await;
+        data.get::<WordFilterKey>()
+            .expect("Expected WordFilter in TypeMap.")
+            .guilds
+            .get(&guild.0)
+            .cloned()
+    };
+    let compiled = match cached {
+        Some(c) => c,
+        None => {
+            let compiled = compile(&guildconfig::settings(ctx, guild).await.filter);
+            let mut data = ctx.data.write().await;
+            data.get_mut::<WordFilterKey>()
+                .expect("Expected WordFilter in TypeMap.")
+                .guilds
+                .insert(guild.0, compiled.clone());
+            compiled
+        }
+    };
+    if compiled.is_empty() {
+        return None;
+    }
+    let text = normalize(text);
+    compiled
+        .iter()
+        .find(|(_, regex)| regex.is_match(&text))
+        .map(|(source, _)| source.clone())
+}
+
+/// Deletes and logs messages containing a blocked word or pattern. Returns
+/// whether the message was removed. The automod exemptions apply here too.
+pub async fn check_message(ctx: &Context, msg: &Message) -> bool {
+    let guild = match msg.guild_id {
+        Some(g) if !msg.author.bot => g,
+        _ => return false,
+    };
+    let automod = guildconfig::settings(ctx, guild).await.automod;
+    let exempt_role = msg.member.as_ref().map_or(false, |m| {
+        m.roles.iter().any(|r| automod.exempt_roles.contains(r))
+    });
+    if exempt_role || automod.exempt_channels.contains(&msg.channel_id) {
+        return false;
+    }
+    let matched = match find_match(ctx, guild, &msg.content).await {
+        Some(m) => m,
+        None => return false,
+    };
+    if let Err(why) = msg.delete(ctx).await {
+        error!("Failed to delete a filtered message: {:?}", why);
+    }
+    modlog::post_deletion(
+        &ctx.http,
+        &ctx.data,
+        guild,
+        msg.author.id,
+        msg.channel_id,
+        &format!("Filter: matched `{}`", matched),
+        &msg.content,
+    )
+    .await;
+    true
+}
+
+/// Replaces a nickname (or username, when there is no nickname) that matches
+/// the filter.
+pub async fn check_nickname(ctx: &Context, member: &Member) {
+    let name = member.nick.as_ref().unwrap_or(&member.user.name);
+    if name == MODERATED_NICKNAME {
+        return;
+    }
+    let matched = match find_match(ctx, member.guild_id, name).await {
+        Some(m) => m,
+        None => return,
+    };
+    if let Err(why) = member
+        .edit(&ctx.http, |m| m.nickname(MODERATED_NICKNAME))
+        .await
+    {
+        error!("Failed to reset a filtered nickname: {:?}", why);
+        return;
+    }
+    modlog::post_nickname(
+        &ctx.http,
+        &ctx.data,
+        member.guild_id,
+        member.user.id,
+        name,
+        &matched,
+    )
+    .await;
+}